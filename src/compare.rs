@@ -0,0 +1,120 @@
+use std::fmt;
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Decides whether typed text matches the expected text. Both scoring and
+/// the word display go through the same policy so they never disagree.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Comparison {
+    ignore_case: bool,
+    ignore_accents: bool,
+}
+
+impl Comparison {
+    pub fn new() -> Comparison {
+        Comparison::default()
+    }
+
+    pub fn with_ignore_case(mut self, ignore_case: bool) -> Comparison {
+        self.ignore_case = ignore_case;
+        self
+    }
+
+    pub fn with_ignore_accents(mut self, ignore_accents: bool) -> Comparison {
+        self.ignore_accents = ignore_accents;
+        self
+    }
+
+    fn fold(&self, grapheme: &str) -> String {
+        let folded: String = if self.ignore_accents {
+            grapheme.nfd().filter(|c| !is_combining_mark(*c)).collect()
+        } else {
+            grapheme.nfc().collect()
+        };
+        if self.ignore_case {
+            folded.to_lowercase()
+        } else {
+            folded
+        }
+    }
+
+    pub fn graphemes_eq(&self, g1: &str, g2: &str) -> bool {
+        g1 == g2 || self.fold(g1) == self.fold(g2)
+    }
+
+    pub fn words_eq(&self, s1: &str, s2: &str) -> bool {
+        s1 == s2 || {
+            let mut g1 = s1.graphemes(true);
+            let mut g2 = s2.graphemes(true);
+            loop {
+                match (g1.next(), g2.next()) {
+                    (Some(x), Some(y)) if self.graphemes_eq(x, y) => continue,
+                    (None, None) => break true,
+                    _ => break false,
+                }
+            }
+        }
+    }
+
+    /// Returns the byte length of the longest prefix of `s1` that matches
+    /// the start of `s2`.
+    pub fn common_prefix_len(&self, s1: &str, s2: &str) -> usize {
+        s1.graphemes(true)
+            .zip(s2.graphemes(true))
+            .take_while(|(x, y)| self.graphemes_eq(x, y))
+            .map(|(x, _)| x.len())
+            .sum()
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.ignore_case, self.ignore_accents) {
+            (false, false) => write!(f, "exact"),
+            (true, false) => write!(f, "ignore case"),
+            (false, true) => write!(f, "ignore accents"),
+            (true, true) => write!(f, "ignore case+accents"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact() {
+        let comparison = Comparison::new();
+        assert!(comparison.words_eq("café", "café"));
+        assert!(comparison.words_eq("café", "cafe\u{301}"));
+        assert!(!comparison.words_eq("café", "cafe"));
+        assert!(!comparison.words_eq("Café", "café"));
+        assert!(!comparison.words_eq("caf", "café"));
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let comparison = Comparison::new().with_ignore_case(true);
+        assert!(comparison.words_eq("ÉCOLE", "école"));
+        assert!(!comparison.words_eq("ecole", "école"));
+    }
+
+    #[test]
+    fn test_ignore_accents() {
+        let comparison = Comparison::new().with_ignore_accents(true);
+        assert!(comparison.words_eq("ecole", "école"));
+        assert!(comparison.words_eq("uber", "über"));
+        assert!(!comparison.words_eq("Ecole", "école"));
+    }
+
+    #[test]
+    fn test_common_prefix_len() {
+        let comparison = Comparison::new()
+            .with_ignore_case(true)
+            .with_ignore_accents(true);
+        assert_eq!(comparison.common_prefix_len("Éco", "ecole"), "Éco".len());
+        assert_eq!(Comparison::new().common_prefix_len("Éco", "ecole"), 0);
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::consts;
+use crate::keyboard::{Keymap, Layout, LayoutError, DEFAULT_LAYOUT};
+use crate::lexicon;
+use crate::lexicon::LexiconFilter;
+use crate::view::{Settings, StyleDefinition, Theme, ThemeDefinition, ThemeError, DEFAULT_THEME};
+
+const CONFIG_FILE: &str = "config.toml";
+
+/// User settings overriding the defaults in `consts`, read from a TOML file.
+/// Missing keys keep their defaults.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub lexicon: String,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    /// Only words made entirely of these characters are kept.
+    pub alphabet: Option<String>,
+    /// A pattern words must match.
+    pub include: Option<String>,
+    /// A pattern words must not match.
+    pub exclude: Option<String>,
+    /// A file listing words to leave out.
+    pub stop_words: Option<String>,
+    pub sample_size: usize,
+    pub panel_cols: usize,
+    pub panel_rows: usize,
+    pub performance_refresh_ms: u64,
+    /// Pauses after this many seconds without typing, or never if 0.
+    pub auto_pause_secs: u64,
+    pub punctuation: bool,
+    pub numbers: bool,
+    pub ignore_case: bool,
+    pub ignore_accents: bool,
+    /// The layout the operating system types with, built in or from a file.
+    pub layout: String,
+    /// A layout to practice by remapping the keys of `layout` to it.
+    pub simulate: Option<String>,
+    /// A built-in theme or a theme file.
+    pub theme: String,
+    /// Overrides the theme's styles.
+    pub styles: StyleDefinition,
+    /// Overrides the theme's palette.
+    pub palette: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            lexicon: consts::INPUT_FILE.to_string(),
+            min_length: None,
+            max_length: None,
+            alphabet: None,
+            include: None,
+            exclude: None,
+            stop_words: None,
+            sample_size: consts::SAMPLE_SIZE,
+            panel_cols: consts::PANEL_COLS,
+            panel_rows: consts::PANEL_ROWS,
+            performance_refresh_ms: consts::PERFORMANCE_REFRESH_MS,
+            auto_pause_secs: consts::AUTO_PAUSE_SECS,
+            punctuation: false,
+            numbers: false,
+            ignore_case: false,
+            ignore_accents: false,
+            layout: DEFAULT_LAYOUT.to_string(),
+            simulate: None,
+            theme: DEFAULT_THEME.to_string(),
+            styles: StyleDefinition::default(),
+            palette: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Where the configuration is read from unless another file is given:
+    /// `fastfinge-rs/config.toml` in the XDG config directory.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(consts::FAST_FINGERS).join(CONFIG_FILE))
+    }
+
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        let config: Config = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Config, ConfigError> {
+        Config::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Checks the settings that are valid TOML but make no sense.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let positive = [
+            ("sample_size", self.sample_size),
+            ("panel_cols", self.panel_cols),
+            ("panel_rows", self.panel_rows),
+        ];
+        for (name, value) in positive.iter() {
+            if *value == 0 {
+                return Err(ConfigError::Invalid(format!("{} must be positive.", name)));
+            }
+        }
+        if self.performance_refresh_ms == 0 {
+            let message = "performance_refresh_ms must be positive.".to_string();
+            return Err(ConfigError::Invalid(message));
+        }
+        if let (Some(min_length), Some(max_length)) = (self.min_length, self.max_length) {
+            if min_length > max_length {
+                let message = "min_length must not exceed max_length.".to_string();
+                return Err(ConfigError::Invalid(message));
+            }
+        }
+        self.pattern("include", &self.include)?;
+        self.pattern("exclude", &self.exclude)?;
+        self.theme()?;
+        self.keymap()?;
+        Ok(())
+    }
+
+    fn pattern(&self, name: &str, pattern: &Option<String>) -> Result<Option<Regex>, ConfigError> {
+        match pattern {
+            Some(pattern) => match Regex::new(pattern) {
+                Ok(regex) => Ok(Some(regex)),
+                Err(err) => Err(ConfigError::Invalid(format!("{}: {}", name, err))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// The lexicon filters, with the stop words read from their file.
+    pub fn lexicon_filter(&self) -> Result<LexiconFilter, ConfigError> {
+        let mut filter = LexiconFilter::new();
+        if let Some(min_length) = self.min_length {
+            filter = filter.with_min_length(min_length);
+        }
+        if let Some(max_length) = self.max_length {
+            filter = filter.with_max_length(max_length);
+        }
+        if let Some(alphabet) = &self.alphabet {
+            filter = filter.with_alphabet(alphabet);
+        }
+        if let Some(include) = self.pattern("include", &self.include)? {
+            filter = filter.with_include(include);
+        }
+        if let Some(exclude) = self.pattern("exclude", &self.exclude)? {
+            filter = filter.with_exclude(exclude);
+        }
+        if let Some(stop_words) = &self.stop_words {
+            let mut reader = BufReader::new(File::open(stop_words)?);
+            filter = filter.with_stop_words(lexicon::read_words(&mut reader)?);
+        }
+        Ok(filter)
+    }
+
+    /// The lexicon filters set, e.g. "min_length=3 alphabet=asdf", or an
+    /// empty string if none.
+    pub fn filter_summary(&self) -> String {
+        let filters = [
+            (
+                "min_length",
+                self.min_length.map(|length| length.to_string()),
+            ),
+            (
+                "max_length",
+                self.max_length.map(|length| length.to_string()),
+            ),
+            ("alphabet", self.alphabet.clone()),
+            ("include", self.include.clone()),
+            ("exclude", self.exclude.clone()),
+            ("stop_words", self.stop_words.clone()),
+        ];
+        filters
+            .iter()
+            .filter_map(|(name, value)| value.as_ref().map(|value| format!("{}={}", name, value)))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// The layout typed with: the simulated one if any.
+    pub fn layout(&self) -> Result<Layout, ConfigError> {
+        Ok(Layout::load(
+            self.simulate.as_ref().unwrap_or(&self.layout),
+        )?)
+    }
+
+    /// Remaps keystrokes when simulating a layout.
+    pub fn keymap(&self) -> Result<Option<Keymap>, ConfigError> {
+        match &self.simulate {
+            Some(simulate) => Ok(Some(Keymap::between(
+                &Layout::load(&self.layout)?,
+                &Layout::load(simulate)?,
+            ))),
+            None => {
+                Layout::load(&self.layout)?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// The theme with the styles and palette of this config applied.
+    pub fn theme(&self) -> Result<Theme, ConfigError> {
+        let mut definition = ThemeDefinition::load(&self.theme)?;
+        definition.merge(&ThemeDefinition {
+            shadow: None,
+            styles: self.styles.clone(),
+            palette: self.palette.clone(),
+        });
+        Ok(definition.build()?)
+    }
+
+    pub fn settings(&self) -> Result<Settings, ConfigError> {
+        Ok(Settings {
+            panel_cols: self.panel_cols,
+            styles: self.theme()?.styles,
+        })
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = toml::to_string(self).map_err(|_| fmt::Error)?;
+        write!(f, "{}", text)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Theme(ThemeError),
+    Layout(LayoutError),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "Could not read config: {}", err),
+            ConfigError::Toml(err) => write!(f, "Invalid config: {}", err),
+            ConfigError::Theme(err) => write!(f, "{}", err),
+            ConfigError::Layout(err) => write!(f, "{}", err),
+            ConfigError::Invalid(message) => write!(f, "Invalid config: {}", message),
+        }
+    }
+}
+
+impl error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ConfigError::Io(err) => Some(err),
+            ConfigError::Toml(err) => Some(err),
+            ConfigError::Theme(err) => Some(err),
+            ConfigError::Layout(err) => Some(err),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> ConfigError {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<LayoutError> for ConfigError {
+    fn from(err: LayoutError) -> ConfigError {
+        ConfigError::Layout(err)
+    }
+}
+
+impl From<ThemeError> for ConfigError {
+    fn from(err: ThemeError) -> ConfigError {
+        ConfigError::Theme(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> ConfigError {
+        ConfigError::Toml(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::{parse_style, Styles};
+
+    #[test]
+    fn test_theme() {
+        let config = Config::parse("theme = \"dark\"\n[palette]\nview = \"blue\"\n").unwrap();
+        let theme = config.theme().unwrap();
+        assert!(!theme.cursive.shadow);
+        assert_eq!(theme.styles.pacer, parse_style("light cyan").unwrap());
+        assert_eq!(
+            theme.cursive.palette[cursive::theme::PaletteColor::View],
+            cursive::theme::Color::Dark(cursive::theme::BaseColor::Blue)
+        );
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(Config::default().keymap().unwrap(), None);
+        let config = Config::parse("layout = \"dvorak\"\nsimulate = \"colemak\"\n").unwrap();
+        assert_eq!(config.layout().unwrap().name(), "colemak");
+        assert_eq!(config.keymap().unwrap().unwrap().map('e'), 's');
+        match Config::parse("simulate = \"azerty\"\n") {
+            Err(ConfigError::Layout(LayoutError::Unknown(_))) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = Config::parse("min_length = 3\ninclude = \"^s\"\n").unwrap();
+        assert_eq!(config.min_length, Some(3));
+        assert!(config.lexicon_filter().is_ok());
+        for text in [
+            "performance_refresh_ms = 0\n",
+            "exclude = \"(\"\n",
+            "min_length = 8\nmax_length = 3\n",
+        ]
+        .iter()
+        {
+            match Config::parse(text) {
+                Err(ConfigError::Invalid(_)) => (),
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::default();
+        assert_eq!(config.settings().unwrap(), Settings::default());
+        assert_eq!(Config::parse("").unwrap(), config);
+        assert_eq!(Config::parse(&config.to_string()).unwrap(), config);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let text = "panel_cols = 40\n\
+                    ignore_case = true\n\
+                    [styles]\n\
+                    incorrect = \"underline magenta\"\n";
+        let config = Config::parse(text).unwrap();
+        assert_eq!(config.panel_cols, 40);
+        assert!(config.ignore_case);
+        assert_eq!(config.panel_rows, consts::PANEL_ROWS);
+        let styles = config.settings().unwrap().styles;
+        assert_eq!(styles.correct, Styles::default().correct);
+        assert_eq!(styles.incorrect, parse_style("underline magenta").unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        match Config::parse("panel_colums = 40\n") {
+            Err(ConfigError::Toml(_)) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        match Config::parse("sample_size = 0\n") {
+            Err(ConfigError::Invalid(_)) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        match Config::parse("[styles]\ncorrect = \"sparkly\"\n") {
+            Err(ConfigError::Theme(ThemeError::Style { name, .. })) => assert_eq!(name, "correct"),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        match Config::parse("theme = \"sparkly\"\n") {
+            Err(ConfigError::Theme(ThemeError::Unknown(_))) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
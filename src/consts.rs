@@ -1,26 +1,31 @@
-use cursive::theme::{BaseColor, Color, Effect, Style};
-
 pub const FAST_FINGERS: &str = "fastfinge-rs";
 
 pub const PANEL_COLS: usize = 60;
 pub const PANEL_ROWS: usize = 2;
 pub const PERFORMANCE_COLS: usize = 30;
-pub const PERFORMANCE_ROWS: usize = 5;
+pub const PERFORMANCE_ROWS: usize = 11;
+pub const SHORT_WINDOW_SECS: u64 = 5;
+pub const LONG_WINDOW_SECS: u64 = 10;
+pub const GRAPH_COLS: usize = 60;
+pub const GRAPH_ROWS: usize = 10;
 pub const PERFORMANCE_REFRESH_MS: u64 = 50;
+pub const TELEMETRY_INTERVAL_MS: u64 = 500;
+pub const TELEMETRY_QUEUE_SIZE: usize = 256;
+pub const TELEMETRY_WRITE_TIMEOUT_MS: u64 = 1000;
+pub const RACE_ROWS: usize = 8;
+pub const SLOWEST_WORDS: usize = 5;
 pub const SAMPLE_SIZE: usize = 100;
+pub const AUTO_PAUSE_SECS: u64 = 0;
 
 pub const INPUT_FILE: &str = "./input/top1000.txt";
+pub const HOST_ADDRESS: &str = "0.0.0.0:7878";
+pub const PLAYER: &str = "player";
+pub const GREETING: &str = "Type quickly.";
+pub const PAUSED: &str = "Paused. Press Esc to resume.";
 
 pub const ENTRY: &str = "entry";
 pub const PERFORMANCE: &str = "performance";
 pub const STACK: &str = "stack";
 pub const CORE: &str = "core";
 pub const DISPLAY: &str = "display";
-
-lazy_static! {
-    pub static ref CORRECT_STYLE: Style = Style::from(Effect::Bold);
-    pub static ref INCORRECT_STYLE: Style =
-        Style::from(Effect::Reverse).combine(Color::Dark(BaseColor::Red));
-    pub static ref CURRENT_STYLE: Style = Style::from(Effect::Reverse);
-    pub static ref FUTURE_STYLE: Style = Style::from(Effect::Simple);
-}
+pub const RACE: &str = "race";
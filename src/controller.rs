@@ -1,38 +1,62 @@
+use std::time::Instant;
+
 use cursive::Cursive;
 
-use crate::model::Model;
-use crate::performance::PerformanceMonitor;
+use crate::session::{Keystroke, Session};
 use crate::view;
 
-pub fn on_keypress<I>(
+pub fn on_keypress(siv: &mut Cursive, session: &mut Session, contents: &str, _cursor: usize) {
+    session.input(contents, session.now());
+    on_refresh(siv, session);
+}
+
+pub fn on_start(siv: &mut Cursive, session: &mut Session) {
+    session
+        .start(session.now())
+        .expect("The performance monitor should not have been started yet.");
+    view::update_model_display(siv, &session.snapshot(session.now()));
+    view::update_display_on_start(siv);
+}
+
+pub fn on_replayed_keystroke(
     siv: &mut Cursive,
-    model: &mut Model<I>,
-    performance_monitor: &mut PerformanceMonitor,
-    mut contents: &str,
-    _cursor: usize,
-) where
-    I: Iterator<Item = String>,
-{
-    if !contents.is_empty() {
-        let keypress: char = contents.chars().last().unwrap();
-        if keypress.is_whitespace() {
-            contents = contents.trim();
-            let expected = &model
-                .get_current_word()
-                .expect("There should be a current word.");
-            model.register(contents);
-            performance_monitor.register(contents, expected);
-            contents = "";
-        }
+    session: &mut Session,
+    keystroke: Keystroke,
+    now: Instant,
+) {
+    session.press(keystroke, now);
+    on_refresh(siv, session);
+}
+
+pub fn on_refresh(siv: &mut Cursive, session: &Session) {
+    let snapshot = session.snapshot(session.now());
+    view::update_model_display(siv, &snapshot);
+    view::update_race_display(siv, &snapshot);
+    view::update_performance_display(siv, session.performance(), session.personal_best());
+}
+
+/// Pauses or resumes the session, hiding the words while paused.
+pub fn on_pause(siv: &mut Cursive, session: &mut Session) {
+    if session.is_paused() {
+        session
+            .resume(session.now())
+            .expect("A paused session should resume.");
+        view::update_display_on_resume(siv);
+    } else if session.pause(session.now()).is_ok() {
+        view::update_display_on_pause(siv);
     }
-    view::update_model_display(siv, model, &contents);
-    view::update_performance_display(siv, performance_monitor);
 }
 
-pub fn on_start<I>(siv: &mut Cursive, model: &Model<I>)
-where
-    I: Iterator<Item = String>,
-{
-    view::update_model_display(siv, model, "");
-    view::update_display_on_start(siv);
+/// Called periodically. Rivals move on their own, so the words are only
+/// redrawn when there are any.
+pub fn on_tick(siv: &mut Cursive, session: &mut Session) {
+    if session.auto_pause(session.now()) {
+        view::update_display_on_pause(siv);
+    }
+    if session.has_rivals() {
+        session.advance(session.now());
+        on_refresh(siv, session);
+    } else {
+        view::update_performance_display(siv, session.performance(), session.personal_best());
+    }
 }
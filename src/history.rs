@@ -0,0 +1,678 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::consts;
+use crate::keyboard::KeyStats;
+use crate::performance::Summary;
+use crate::session::{Session, Submission};
+
+const HISTORY_FILE: &str = "history.jsonl";
+
+/// How long a word took to type, from its first keystroke to its submission.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WordTime {
+    pub word: String,
+    pub correct: bool,
+    pub duration_ms: u64,
+}
+
+impl WordTime {
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+}
+
+impl From<&Submission> for WordTime {
+    fn from(submission: &Submission) -> WordTime {
+        WordTime {
+            word: submission.expected.clone(),
+            correct: submission.correct,
+            duration_ms: submission.duration.as_millis() as u64,
+        }
+    }
+}
+
+/// A finished session, as kept in the history file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// Seconds since the Unix epoch when the session ended.
+    pub timestamp: u64,
+    pub source: String,
+    pub mode: String,
+    #[serde(default)]
+    pub variant: Variant,
+    /// The seed of the word sequence, if it can be reproduced.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(flatten)]
+    pub summary: Summary,
+    pub words: Vec<WordTime>,
+    #[serde(default)]
+    pub keys: KeyStats,
+}
+
+impl Record {
+    pub fn new(session: &Session, time: SystemTime) -> Record {
+        let performance = session.performance();
+        Record {
+            timestamp: time
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            source: session.model().source().name().to_owned(),
+            mode: performance.comparison().to_string(),
+            variant: session.variant(),
+            seed: None,
+            summary: performance.summary(),
+            words: session.submissions().iter().map(WordTime::from).collect(),
+            keys: session.keys().clone(),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Record {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// What sets a session apart from others of the same mode and source: how
+/// its words were drawn and what it raced against.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Variant {
+    pub punctuation: bool,
+    pub numbers: bool,
+    /// The lexicon filters, e.g. "min_length=3", or empty if none.
+    pub filters: String,
+    /// The pacer's speed, if one was raced.
+    pub pacer: Option<String>,
+    pub ghost: bool,
+    /// Whether other players were raced.
+    pub race: bool,
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pacer = self.pacer.as_ref().map(|pacer| format!("pacer {}", pacer));
+        let parts: Vec<String> = [
+            Some("punctuation".to_string()).filter(|_| self.punctuation),
+            Some("numbers".to_string()).filter(|_| self.numbers),
+            Some(self.filters.clone()).filter(|filters| !filters.is_empty()),
+            pacer,
+            Some("ghost".to_string()).filter(|_| self.ghost),
+            Some("race".to_string()).filter(|_| self.race),
+        ]
+        .iter()
+        .flatten()
+        .cloned()
+        .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Sessions are compared with others rounded up to the same of these
+/// lengths, or to the same minute when longer.
+const LENGTHS_SECS: [u64; 4] = [15, 30, 60, 120];
+
+/// The sessions a personal best is held over: those with the same mode,
+/// lexicon and variant and of about the same length.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Category {
+    pub mode: String,
+    pub source: String,
+    pub variant: Variant,
+    pub length_secs: u64,
+}
+
+impl Category {
+    pub fn new(mode: &str, source: &str, variant: &Variant, duration: Duration) -> Category {
+        let secs = duration.as_millis().div_ceil(1000) as u64;
+        let length_secs = LENGTHS_SECS
+            .iter()
+            .copied()
+            .find(|length| secs <= *length)
+            .unwrap_or_else(|| secs.div_ceil(60) * 60);
+        Category {
+            mode: mode.to_owned(),
+            source: source.to_owned(),
+            variant: variant.clone(),
+            length_secs,
+        }
+    }
+
+    /// Whether `other` only differs in length.
+    fn is_like(&self, other: &Category) -> bool {
+        self.mode == other.mode && self.source == other.source && self.variant == other.variant
+    }
+}
+
+impl From<&Record> for Category {
+    fn from(record: &Record) -> Category {
+        Category::new(
+            &record.mode,
+            &record.source,
+            &record.variant,
+            record.summary.duration(),
+        )
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, {}, ", self.mode, self.source)?;
+        if self.variant != Variant::default() {
+            write!(f, "{}, ", self.variant)?;
+        }
+        write!(f, "up to {}s", self.length_secs)
+    }
+}
+
+/// The fastest session of each category.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PersonalBests(HashMap<Category, Summary>);
+
+impl PersonalBests {
+    pub fn new<'a, I>(records: I) -> PersonalBests
+    where
+        I: IntoIterator<Item = &'a Record>,
+    {
+        let mut bests = PersonalBests::default();
+        for record in records {
+            bests.add(record);
+        }
+        bests
+    }
+
+    /// Keeps `record` if it is the fastest of its category so far.
+    pub fn add(&mut self, record: &Record) {
+        let best = self
+            .0
+            .entry(Category::from(record))
+            .or_insert(record.summary);
+        if record.summary.wpm > best.wpm {
+            *best = record.summary;
+        }
+    }
+
+    pub fn get(&self, category: &Category) -> Option<&Summary> {
+        self.0.get(category)
+    }
+
+    /// The fastest session like those of `category`, whatever its length.
+    pub fn fastest(&self, category: &Category) -> Option<&Summary> {
+        self.0
+            .iter()
+            .filter(|(other, _)| other.is_like(category))
+            .map(|(_, best)| best)
+            .max_by(|x, y| x.wpm.partial_cmp(&y.wpm).unwrap_or(Ordering::Equal))
+    }
+}
+
+/// The columns of an exported record, kept stable for spreadsheets.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Row {
+    pub date: String,
+    pub mode: String,
+    pub lexicon: String,
+    pub seed: Option<u64>,
+    pub wpm: f32,
+    pub raw_wpm: f32,
+    pub accuracy: f32,
+    /// In seconds.
+    pub duration: f32,
+    pub errors: u32,
+    pub variant: Variant,
+}
+
+impl From<&Record> for Row {
+    fn from(record: &Record) -> Row {
+        Row {
+            date: format_timestamp(record.timestamp),
+            mode: record.mode.clone(),
+            lexicon: record.source.clone(),
+            seed: record.seed,
+            wpm: record.summary.wpm,
+            raw_wpm: record.summary.raw_wpm,
+            accuracy: record.summary.accuracy,
+            duration: record.summary.duration().as_secs_f32(),
+            errors: record.summary.errors(),
+            variant: record.variant.clone(),
+        }
+    }
+}
+
+const CSV_HEADER: &str = "date,mode,lexicon,seed,wpm,raw_wpm,accuracy,duration,errors,variant";
+
+pub fn to_csv(records: &[Record]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+    for row in records.iter().map(Row::from) {
+        let fields = [
+            row.date,
+            csv_field(&row.mode),
+            csv_field(&row.lexicon),
+            row.seed.map(|seed| seed.to_string()).unwrap_or_default(),
+            format!("{:.2}", row.wpm),
+            format!("{:.2}", row.raw_wpm),
+            format!("{:.4}", row.accuracy),
+            format!("{:.3}", row.duration),
+            row.errors.to_string(),
+            csv_field(&serde_json::to_string(&row.variant).expect("Variants are serializable.")),
+        ];
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+pub fn to_json(records: &[Record]) -> Result<String, HistoryError> {
+    let rows: Vec<Row> = records.iter().map(Row::from).collect();
+    Ok(serde_json::to_string_pretty(&rows)?)
+}
+
+/// Quotes `field` if it holds a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Reads a date and time written by `format_timestamp`.
+pub fn parse_timestamp(date: &str) -> Option<u64> {
+    let date = date.strip_suffix('Z')?;
+    let (day, time) = date.split_at(date.find('T')?);
+    let mut day = day.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (day.next()??, day.next()??, day.next()??);
+    let mut time = time[1..]
+        .splitn(3, ':')
+        .map(|part| part.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hours > 23
+        || minutes > 59
+        || seconds > 59
+    {
+        return None;
+    }
+    // The inverse of the conversion in `format_timestamp`.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    if days < 0 {
+        return None;
+    }
+    Some(days as u64 * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Seconds since the Unix epoch as an ISO 8601 date and time in UTC.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    // Converts days since 1970-01-01 to a civil date, with March as the
+    // first month of the year so that leap days come last.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// The sessions played so far, one JSON record per line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    /// `fastfinge-rs/history.jsonl` in the XDG data directory.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(consts::FAST_FINGERS).join(HISTORY_FILE))
+    }
+
+    pub fn new(path: &Path) -> History {
+        History {
+            path: path.to_owned(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, record: &Record) -> Result<(), HistoryError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(record)?)?;
+        Ok(())
+    }
+
+    /// Replaces the whole history with `records`.
+    pub fn save(&self, records: &[Record]) -> Result<(), HistoryError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut text = String::new();
+        for record in records {
+            text.push_str(&serde_json::to_string(record)?);
+            text.push('\n');
+        }
+        let temporary = self.path.with_extension("jsonl.tmp");
+        fs::write(&temporary, text)?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+
+    /// Every record, oldest first. A missing file is an empty history.
+    pub fn records(&self) -> Result<Vec<Record>, HistoryError> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        text.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(HistoryError::from))
+            .collect()
+    }
+}
+
+/// The words that took longest on average to type correctly, with the
+/// number of times each was typed, slowest first.
+pub fn slowest_words<'a, I>(words: I, count: usize) -> Vec<(String, Duration, usize)>
+where
+    I: IntoIterator<Item = &'a WordTime>,
+{
+    let mut totals: HashMap<&str, (Duration, usize)> = HashMap::new();
+    for word in words.into_iter().filter(|word| word.correct) {
+        let total = totals.entry(&word.word).or_default();
+        total.0 += word.duration();
+        total.1 += 1;
+    }
+    let mut averages: Vec<_> = totals
+        .into_iter()
+        .map(|(word, (total, times))| (word.to_owned(), total / times as u32, times))
+        .collect();
+    averages.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    averages.truncate(count);
+    averages
+}
+
+#[derive(Debug)]
+pub enum HistoryError {
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryError::Io(err) => write!(f, "Could not access history: {}", err),
+            HistoryError::Json(err) => write!(f, "Invalid history: {}", err),
+        }
+    }
+}
+
+impl error::Error for HistoryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            HistoryError::Io(err) => Some(err),
+            HistoryError::Json(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for HistoryError {
+    fn from(err: io::Error) -> HistoryError {
+        HistoryError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for HistoryError {
+    fn from(err: serde_json::Error) -> HistoryError {
+        HistoryError::Json(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelBuilder;
+    use crate::performance::PerformanceMonitor;
+    use std::env;
+    use std::time::Instant;
+
+    fn word(word: &str, correct: bool, duration_ms: u64) -> WordTime {
+        WordTime {
+            word: word.to_string(),
+            correct,
+            duration_ms,
+        }
+    }
+
+    fn record() -> Record {
+        Record {
+            timestamp: 1_792_396_145,
+            source: "top1000".to_string(),
+            mode: "exact".to_string(),
+            variant: Variant::default(),
+            seed: Some(42),
+            summary: Summary {
+                wpm: 48f32,
+                raw_wpm: 60f32,
+                accuracy: 0.8,
+                correct: 4,
+                attempted: 5,
+                duration_ms: 5000,
+            },
+            words: vec![word("sphinx", true, 600)],
+            keys: KeyStats::new(),
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let model = ModelBuilder::new()
+            .with_word_stream(vec!["sphinx".to_string(), "of".to_string()].into_iter())
+            .build();
+        let mut session = Session::new(model, PerformanceMonitor::new());
+        let start = Instant::now();
+        session.start(start).unwrap();
+        session.input("s", start + Duration::from_millis(100));
+        session.input("sphinx ", start + Duration::from_millis(700));
+        session.end(start + Duration::from_secs(1)).unwrap();
+
+        let record = Record::new(&session, UNIX_EPOCH + Duration::from_secs(42));
+        assert_eq!(record.timestamp, 42);
+        assert_eq!(record.mode, "exact");
+        assert_eq!(record.seed, None);
+        assert_eq!(record.summary.wpm, 60f32);
+        assert_eq!(record.summary.duration_ms, 1000);
+        assert_eq!(record.clone().with_seed(7).seed, Some(7));
+        assert_eq!(record.words, vec![word("sphinx", true, 600)]);
+    }
+
+    #[test]
+    fn test_history() {
+        let path = env::temp_dir()
+            .join(format!("fastfinge-rs-{}", std::process::id()))
+            .join(HISTORY_FILE);
+        let history = History::new(&path);
+        assert_eq!(history.records().unwrap(), vec![]);
+        let record = record();
+        history.append(&record).unwrap();
+        history.append(&record).unwrap();
+        assert_eq!(
+            history.records().unwrap(),
+            vec![record.clone(), record.clone()]
+        );
+        history.save(std::slice::from_ref(&record)).unwrap();
+        assert_eq!(history.records().unwrap(), vec![record]);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_slowest_words() {
+        let words = vec![
+            word("the", true, 200),
+            word("sphinx", true, 900),
+            word("sphinx", true, 700),
+            word("quartz", false, 5000),
+            word("of", true, 300),
+        ];
+        assert_eq!(
+            slowest_words(&words, 2),
+            vec![
+                ("sphinx".to_string(), Duration::from_millis(800), 2),
+                ("of".to_string(), Duration::from_millis(300), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_old_records() {
+        let line = "{\"timestamp\":42,\"source\":\"lex\",\"mode\":\"exact\",\"wpm\":60.0,\
+                    \"accuracy\":1.0,\"duration_ms\":1000,\"words\":[]}";
+        let record: Record = serde_json::from_str(line).unwrap();
+        assert_eq!(record.seed, None);
+        assert_eq!(record.summary.wpm, 60f32);
+        assert_eq!(record.summary.raw_wpm, 0f32);
+        assert!(record.keys.is_empty());
+    }
+
+    #[test]
+    fn test_export() {
+        let mut quoted = record();
+        quoted.source = "my, \"words\"".to_string();
+        quoted.seed = None;
+        assert_eq!(
+            to_csv(&[record(), quoted]),
+            format!(
+                "date,mode,lexicon,seed,wpm,raw_wpm,accuracy,duration,errors,variant\n\
+                 2026-10-19T07:49:05Z,exact,top1000,42,48.00,60.00,0.8000,5.000,1,{0}\n\
+                 2026-10-19T07:49:05Z,exact,\"my, \"\"words\"\"\",,48.00,60.00,0.8000,5.000,1,{0}\n",
+                "\"{\"\"punctuation\"\":false,\"\"numbers\"\":false,\"\"filters\"\":\"\"\"\",\"\"pacer\"\":null,\"\"ghost\"\":false,\"\"race\"\":false}\""
+            )
+        );
+        let json: serde_json::Value = serde_json::from_str(&to_json(&[record()]).unwrap()).unwrap();
+        assert_eq!(json[0]["date"], "2026-10-19T07:49:05Z");
+        assert_eq!(json[0]["lexicon"], "top1000");
+        assert_eq!(json[0]["errors"], 1);
+        assert_eq!(json[0]["variant"]["punctuation"], false);
+    }
+
+    #[test]
+    fn test_personal_bests() {
+        let category = |secs| {
+            let variant = Variant::default();
+            Category::new("exact", "top1000", &variant, Duration::from_millis(secs))
+        };
+        assert_eq!(category(0).length_secs, 15);
+        assert_eq!(category(15_000).length_secs, 15);
+        assert_eq!(category(15_001).length_secs, 30);
+        assert_eq!(category(125_000).length_secs, 180);
+        assert_eq!(category(45_000).to_string(), "exact, top1000, up to 60s");
+
+        let mut slow = record();
+        slow.summary.wpm = 40f32;
+        let mut fast = record();
+        fast.summary.wpm = 50f32;
+        let mut other = record();
+        other.summary.wpm = 60f32;
+        other.summary.duration_ms = 60_000;
+        let bests = PersonalBests::new(&[slow.clone(), fast.clone(), slow.clone(), other]);
+        assert_eq!(bests.get(&Category::from(&slow)), Some(&fast.summary));
+        assert_eq!(
+            bests.get(&category(50_000)).map(|best| best.wpm),
+            Some(60f32)
+        );
+        assert_eq!(bests.get(&category(200_000)), None);
+        assert_eq!(
+            bests.fastest(&category(200_000)).map(|best| best.wpm),
+            Some(60f32)
+        );
+
+        let mut paced = record();
+        paced.variant = Variant {
+            punctuation: true,
+            pacer: Some("60 wpm".to_string()),
+            ..Variant::default()
+        };
+        let paced_category = Category::from(&paced);
+        assert_eq!(
+            paced_category.to_string(),
+            "exact, top1000, punctuation, pacer 60 wpm, up to 15s"
+        );
+        assert_eq!(bests.get(&paced_category), None);
+        assert_eq!(bests.fastest(&paced_category), None);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_709_251_199), "2024-02-29T23:59:59Z");
+        for timestamp in [0, 951_782_400, 1_709_251_199, 1_792_396_145].iter() {
+            assert_eq!(
+                parse_timestamp(&format_timestamp(*timestamp)),
+                Some(*timestamp)
+            );
+        }
+        assert_eq!(parse_timestamp("2024-13-01T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("2024-02-31T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("2023-02-29T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("2100-02-29T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("2024-04-31T00:00:00Z"), None);
+        assert!(parse_timestamp("2024-02-29T00:00:00Z").is_some());
+        assert!(parse_timestamp("2000-02-29T00:00:00Z").is_some());
+        assert_eq!(parse_timestamp("2024-01-01 00:00:00"), None);
+    }
+}
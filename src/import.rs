@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+
+use crate::history::{parse_timestamp, Record, Variant};
+use crate::keyboard::KeyStats;
+use crate::performance::Summary;
+
+/// The mode of results imported from Monkeytype.
+pub const MONKEYTYPE_MODE: &str = "monkeytype";
+
+/// The result files that can be imported, told apart by their header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// The CSV written by `fastfinge-rs export`.
+    FastFingers,
+    /// The results CSV from Monkeytype's account settings.
+    Monkeytype,
+}
+
+impl Format {
+    fn detect(header: &HashMap<&str, usize>) -> Option<Format> {
+        let has = |columns: &[&str]| columns.iter().all(|column| header.contains_key(column));
+        if has(&["date", "lexicon", "wpm", "raw_wpm", "duration", "errors"]) {
+            Some(Format::FastFingers)
+        } else if has(&["wpm", "acc", "rawWpm", "testDuration", "timestamp"]) {
+            Some(Format::Monkeytype)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::FastFingers => write!(f, "fastfinge-rs"),
+            Format::Monkeytype => write!(f, "Monkeytype"),
+        }
+    }
+}
+
+/// One line of a CSV file with its columns looked up by name.
+struct Line<'a> {
+    number: usize,
+    header: &'a HashMap<&'a str, usize>,
+    fields: Vec<String>,
+}
+
+impl<'a> Line<'a> {
+    fn get(&self, column: &str) -> &str {
+        self.header
+            .get(column)
+            .and_then(|index| self.fields.get(*index))
+            .map_or("", |field| field.trim())
+    }
+
+    fn parse<T: std::str::FromStr>(&self, column: &str) -> Result<T, ImportError> {
+        self.get(column).parse().map_err(|_| ImportError::Invalid {
+            line: self.number,
+            column: column.to_owned(),
+            value: self.get(column).to_owned(),
+        })
+    }
+}
+
+/// Reads the records of a result file in any known format.
+pub fn import(text: &str) -> Result<(Format, Vec<Record>), ImportError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+    let header_fields = match lines.next() {
+        Some((_, line)) => split_csv_line(line),
+        None => return Err(ImportError::UnknownFormat),
+    };
+    let header: HashMap<&str, usize> = header_fields
+        .iter()
+        .enumerate()
+        .map(|(index, column)| (column.trim(), index))
+        .collect();
+    let format = Format::detect(&header).ok_or(ImportError::UnknownFormat)?;
+    let records = lines
+        .map(|(index, line)| {
+            let line = Line {
+                number: index + 1,
+                header: &header,
+                fields: split_csv_line(line),
+            };
+            match format {
+                Format::FastFingers => from_fast_fingers(&line),
+                Format::Monkeytype => from_monkeytype(&line),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok((format, records))
+}
+
+fn record(timestamp: u64, source: &str, mode: &str, wpm: f32, raw_wpm: f32) -> Record {
+    Record {
+        timestamp,
+        source: source.to_owned(),
+        mode: mode.to_owned(),
+        variant: Variant::default(),
+        seed: None,
+        summary: Summary {
+            wpm,
+            raw_wpm,
+            ..Summary::default()
+        },
+        words: Vec::new(),
+        keys: KeyStats::new(),
+    }
+}
+
+/// Estimates the words attempted and typed correctly from the speeds, as
+/// neither format lists them.
+fn count_words(summary: &mut Summary, errors: Option<u32>) {
+    let minutes = summary.duration().as_secs_f32() / 60f32;
+    summary.attempted = (summary.raw_wpm * minutes).round() as u32;
+    summary.correct = match errors {
+        Some(errors) => summary.attempted.saturating_sub(errors),
+        None => ((summary.wpm * minutes).round() as u32).min(summary.attempted),
+    };
+}
+
+fn from_fast_fingers(line: &Line) -> Result<Record, ImportError> {
+    let timestamp = parse_timestamp(line.get("date")).ok_or_else(|| ImportError::Invalid {
+        line: line.number,
+        column: "date".to_owned(),
+        value: line.get("date").to_owned(),
+    })?;
+    let mut record = record(
+        timestamp,
+        line.get("lexicon"),
+        line.get("mode"),
+        line.parse("wpm")?,
+        line.parse("raw_wpm")?,
+    );
+    if !line.get("seed").is_empty() {
+        record.seed = Some(line.parse("seed")?);
+    }
+    // Exports from before variants were kept have no such column.
+    if !line.get("variant").is_empty() {
+        record.variant =
+            serde_json::from_str(line.get("variant")).map_err(|_| ImportError::Invalid {
+                line: line.number,
+                column: "variant".to_owned(),
+                value: line.get("variant").to_owned(),
+            })?;
+    }
+    record.summary.accuracy = line.parse("accuracy")?;
+    record.summary.duration_ms = (line.parse::<f32>("duration")? * 1000f32).round() as u64;
+    count_words(&mut record.summary, Some(line.parse("errors")?));
+    Ok(record)
+}
+
+/// Monkeytype counts five characters as a word rather than whole words, so
+/// its results are kept apart under their own mode instead of competing
+/// with sessions typed here.
+fn from_monkeytype(line: &Line) -> Result<Record, ImportError> {
+    let source = match line.get("language") {
+        "" => "monkeytype",
+        language => language,
+    };
+    let mut record = record(
+        line.parse::<u64>("timestamp")? / 1000,
+        source,
+        MONKEYTYPE_MODE,
+        line.parse("wpm")?,
+        line.parse("rawWpm")?,
+    );
+    record.summary.accuracy = line.parse::<f32>("acc")? / 100f32;
+    record.summary.duration_ms = (line.parse::<f32>("testDuration")? * 1000f32).round() as u64;
+    count_words(&mut record.summary, None);
+    Ok(record)
+}
+
+/// Adds the imported records that are not in `records` yet, returning how
+/// many. Speeds are rounded on export, so records are told apart by when
+/// they ended and what was typed.
+pub fn merge(records: &mut Vec<Record>, imported: Vec<Record>) -> usize {
+    let mut added = 0;
+    for record in imported {
+        let duplicate = records.iter().any(|existing| {
+            existing.timestamp == record.timestamp
+                && existing.source == record.source
+                && existing.mode == record.mode
+        });
+        if !duplicate {
+            records.push(record);
+            added += 1;
+        }
+    }
+    added
+}
+
+/// Splits a line of CSV on commas outside double quotes, undoubling quotes.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    UnknownFormat,
+    Invalid {
+        line: usize,
+        column: String,
+        value: String,
+    },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::UnknownFormat => write!(
+                f,
+                "Unknown file format, expected a CSV exported by {} or {}",
+                Format::FastFingers,
+                Format::Monkeytype
+            ),
+            ImportError::Invalid {
+                line,
+                column,
+                value,
+            } => write!(f, "Line {}: invalid {} \"{}\"", line, column, value),
+        }
+    }
+}
+
+impl error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history;
+
+    #[test]
+    fn test_split_csv_line() {
+        assert_eq!(split_csv_line("a,,b"), vec!["a", "", "b"]);
+        assert_eq!(
+            split_csv_line("\"my, \"\"words\"\"\",1"),
+            vec!["my, \"words\"", "1"]
+        );
+    }
+
+    #[test]
+    fn test_monkeytype() {
+        let text = "_id,isPb,wpm,acc,rawWpm,consistency,charStats,mode,mode2,quoteLength,\
+                    restartCount,testDuration,afkDuration,incompleteTestSeconds,lazyMode,\
+                    blindMode,bailedOut,tags,timestamp,language\n\
+                    65f0,true,60.5,97.5,66,80.1,150;3;0;1,time,30,-1,0,30,0,0,false,false,\
+                    false,,1710000000123,english\n";
+        let (format, records) = import(text).unwrap();
+        assert_eq!(format, Format::Monkeytype);
+        let record = &records[0];
+        assert_eq!(record.timestamp, 1_710_000_000);
+        assert_eq!(record.source, "english");
+        assert_eq!(record.mode, MONKEYTYPE_MODE);
+        assert_eq!(record.summary.wpm, 60.5);
+        assert_eq!(record.summary.accuracy, 0.975);
+        assert_eq!(record.summary.duration_ms, 30000);
+        assert_eq!(record.summary.attempted, 33);
+        assert_eq!(record.summary.correct, 30);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut record = record(1_792_396_145, "my, \"words\"", "ignore case", 48f32, 60f32);
+        record.seed = Some(42);
+        record.variant = Variant {
+            punctuation: true,
+            pacer: Some("60 wpm".to_string()),
+            ..Variant::default()
+        };
+        record.summary.accuracy = 0.8;
+        record.summary.duration_ms = 5000;
+        count_words(&mut record.summary, Some(1));
+        let (format, records) = import(&history::to_csv(std::slice::from_ref(&record))).unwrap();
+        assert_eq!(format, Format::FastFingers);
+        assert_eq!(records, vec![record]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut existing = record(1_792_396_145, "top1000", "exact", 48.123, 60.456);
+        existing.summary.accuracy = 0.8;
+        existing.summary.duration_ms = 5000;
+        let mut records = vec![existing];
+        let (_, imported) = import(&history::to_csv(&records)).unwrap();
+        assert_ne!(imported[0].summary.wpm, records[0].summary.wpm);
+        assert_eq!(merge(&mut records, imported), 0);
+        assert_eq!(records.len(), 1);
+
+        let later = record(1_792_396_200, "top1000", "exact", 50.0, 60.0);
+        assert_eq!(merge(&mut records, vec![later]), 1);
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(matches!(import(""), Err(ImportError::UnknownFormat)));
+        assert!(matches!(
+            import("a,b\n1,2\n"),
+            Err(ImportError::UnknownFormat)
+        ));
+        let text = "date,mode,lexicon,seed,wpm,raw_wpm,accuracy,duration,errors\n\
+                    yesterday,exact,top1000,,1,1,1,1,0\n";
+        match import(text) {
+            Err(ImportError::Invalid { line, column, .. }) => {
+                assert_eq!(line, 2);
+                assert_eq!(column, "date");
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
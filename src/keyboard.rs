@@ -0,0 +1,403 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_LAYOUT: &str = "qwerty";
+
+const BUILT_IN_LAYOUTS: [(&str, [&str; 4]); 4] = [
+    (
+        "qwerty",
+        ["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"],
+    ),
+    (
+        "dvorak",
+        ["1234567890[]", "',.pyfgcrl/=", "aoeuidhtns-", ";qjkxbmwvz"],
+    ),
+    (
+        "colemak",
+        ["1234567890-=", "qwfpgjluy;[]", "arstdhneio'", "zxcvbkm,./"],
+    ),
+    (
+        "workman",
+        ["1234567890-=", "qdrwbjfup;[]", "ashtgyneoi'", "zxmcvkl,./"],
+    ),
+];
+
+/// The finger pressing each column of a row in touch typing.
+const COLUMN_FINGERS: [Finger; 12] = [
+    Finger::LeftPinky,
+    Finger::LeftRing,
+    Finger::LeftMiddle,
+    Finger::LeftIndex,
+    Finger::LeftIndex,
+    Finger::RightIndex,
+    Finger::RightIndex,
+    Finger::RightMiddle,
+    Finger::RightRing,
+    Finger::RightPinky,
+    Finger::RightPinky,
+    Finger::RightPinky,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Hand {
+    Left,
+    Right,
+}
+
+impl fmt::Display for Hand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Hand::Left => write!(f, "left hand"),
+            Hand::Right => write!(f, "right hand"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Finger {
+    LeftPinky,
+    LeftRing,
+    LeftMiddle,
+    LeftIndex,
+    RightIndex,
+    RightMiddle,
+    RightRing,
+    RightPinky,
+}
+
+impl Finger {
+    pub fn hand(self) -> Hand {
+        match self {
+            Finger::LeftPinky | Finger::LeftRing | Finger::LeftMiddle | Finger::LeftIndex => {
+                Hand::Left
+            }
+            _ => Hand::Right,
+        }
+    }
+}
+
+impl fmt::Display for Finger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Finger::LeftPinky | Finger::RightPinky => "pinky",
+            Finger::LeftRing | Finger::RightRing => "ring",
+            Finger::LeftMiddle | Finger::RightMiddle => "middle",
+            Finger::LeftIndex | Finger::RightIndex => "index",
+        };
+        match self.hand() {
+            Hand::Left => write!(f, "left {}", name),
+            Hand::Right => write!(f, "right {}", name),
+        }
+    }
+}
+
+/// The unshifted characters of a keyboard, row by row from the number row,
+/// each row starting at its leftmost key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Layout {
+    name: String,
+    rows: Vec<String>,
+}
+
+impl Layout {
+    pub fn new(name: &str, rows: Vec<String>) -> Result<Layout, LayoutError> {
+        let mut seen = HashSet::new();
+        if rows.is_empty() {
+            return Err(LayoutError::Invalid(format!("{} has no keys.", name)));
+        }
+        for c in rows.iter().flat_map(|row| row.chars()) {
+            if c.is_whitespace() || !seen.insert(c) {
+                let message = format!("{} has \"{}\" more than once or as a key.", name, c);
+                return Err(LayoutError::Invalid(message));
+            }
+        }
+        Ok(Layout {
+            name: name.to_owned(),
+            rows,
+        })
+    }
+
+    pub fn built_in(name: &str) -> Option<Layout> {
+        BUILT_IN_LAYOUTS
+            .iter()
+            .find(|(built_in, _)| *built_in == name)
+            .map(|(name, rows)| Layout {
+                name: name.to_string(),
+                rows: rows.iter().map(|row| row.to_string()).collect(),
+            })
+    }
+
+    pub fn built_in_names() -> Vec<&'static str> {
+        BUILT_IN_LAYOUTS.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// A built-in layout, or a file with one row of keys per line.
+    pub fn load(name_or_path: &str) -> Result<Layout, LayoutError> {
+        if let Some(layout) = Layout::built_in(name_or_path) {
+            return Ok(layout);
+        }
+        let path = Path::new(name_or_path);
+        if !path.is_file() {
+            return Err(LayoutError::Unknown(name_or_path.to_owned()));
+        }
+        let rows = fs::read_to_string(path)?
+            .lines()
+            .map(|line| line.split_whitespace().collect::<String>())
+            .filter(|row| !row.is_empty())
+            .collect();
+        Layout::new(name_or_path, rows)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn rows(&self) -> &[String] {
+        &self.rows
+    }
+
+    /// The row and column of the key typing `c`, ignoring case.
+    pub fn position(&self, c: char) -> Option<(usize, usize)> {
+        let key = c.to_lowercase().next().unwrap_or(c);
+        self.rows.iter().enumerate().find_map(|(row, keys)| {
+            keys.chars()
+                .position(|other| other == key)
+                .map(|col| (row, col))
+        })
+    }
+
+    /// The finger typing `c` by its column, the two central columns going to
+    /// the index fingers and any past the tenth to the right pinky.
+    pub fn finger(&self, c: char) -> Option<Finger> {
+        self.position(c)
+            .map(|(_, col)| COLUMN_FINGERS[col.min(COLUMN_FINGERS.len() - 1)])
+    }
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout::built_in(DEFAULT_LAYOUT).expect("The default layout is built in.")
+    }
+}
+
+/// Turns what the operating system's layout types into what another layout
+/// would type with the same keys, to practice a layout without switching to
+/// it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Keymap(HashMap<char, char>);
+
+impl Keymap {
+    pub fn between(from: &Layout, to: &Layout) -> Keymap {
+        let mut keys = HashMap::new();
+        for (from_row, to_row) in from.rows.iter().zip(to.rows.iter()) {
+            keys.extend(from_row.chars().zip(to_row.chars()));
+        }
+        Keymap(keys)
+    }
+
+    /// Maps `c` keeping its case. Characters typed with shift other than
+    /// capitals, and those not on the layout, are left as they are.
+    pub fn map(&self, c: char) -> char {
+        if let Some(mapped) = self.0.get(&c) {
+            return *mapped;
+        }
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        match self.0.get(&lower) {
+            Some(mapped) if lower != c => mapped.to_uppercase().next().unwrap_or(*mapped),
+            _ => c,
+        }
+    }
+}
+
+/// How often a key was pressed, mistyped, and how long it took.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyStat {
+    pub presses: u32,
+    pub errors: u32,
+    /// The total time since the previous keystroke, over `timed` presses.
+    pub latency_ms: u64,
+    pub timed: u32,
+}
+
+impl KeyStat {
+    pub fn add(&mut self, other: &KeyStat) {
+        self.presses += other.presses;
+        self.errors += other.errors;
+        self.latency_ms += other.latency_ms;
+        self.timed += other.timed;
+    }
+
+    pub fn accuracy(&self) -> Option<f32> {
+        self.error_rate().map(|rate| 1f32 - rate)
+    }
+
+    pub fn error_rate(&self) -> Option<f32> {
+        match self.presses {
+            0 => None,
+            _ => Some(self.errors as f32 / self.presses as f32),
+        }
+    }
+
+    pub fn average_latency(&self) -> Option<Duration> {
+        match self.timed {
+            0 => None,
+            _ => Some(Duration::from_millis(self.latency_ms / self.timed as u64)),
+        }
+    }
+}
+
+/// Statistics for each key, attributed to the key that should have been
+/// pressed. Letters are counted without case.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyStats(BTreeMap<char, KeyStat>);
+
+impl KeyStats {
+    pub fn new() -> KeyStats {
+        KeyStats(BTreeMap::new())
+    }
+
+    pub fn record(&mut self, expected: char, correct: bool, latency: Option<Duration>) {
+        let key = expected.to_lowercase().next().unwrap_or(expected);
+        let stat = self.0.entry(key).or_default();
+        stat.presses += 1;
+        if !correct {
+            stat.errors += 1;
+        }
+        if let Some(latency) = latency {
+            stat.latency_ms += latency.as_millis() as u64;
+            stat.timed += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &KeyStats) {
+        for (key, other) in other.0.iter() {
+            self.0.entry(*key).or_default().add(other);
+        }
+    }
+
+    pub fn get(&self, key: char) -> Option<&KeyStat> {
+        self.0.get(&key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (char, &KeyStat)> {
+        self.0.iter().map(|(key, stat)| (*key, stat))
+    }
+}
+
+#[derive(Debug)]
+pub enum LayoutError {
+    Io(io::Error),
+    Unknown(String),
+    Invalid(String),
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::Io(err) => write!(f, "Could not read layout: {}", err),
+            LayoutError::Unknown(name) => write!(
+                f,
+                "Unknown layout \"{}\", expected a file or one of: {}",
+                name,
+                Layout::built_in_names().join(", ")
+            ),
+            LayoutError::Invalid(message) => write!(f, "Invalid layout: {}", message),
+        }
+    }
+}
+
+impl error::Error for LayoutError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LayoutError::Io(err) => Some(err),
+            LayoutError::Unknown(_) | LayoutError::Invalid(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LayoutError {
+    fn from(err: io::Error) -> LayoutError {
+        LayoutError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layouts() {
+        for name in Layout::built_in_names() {
+            let layout = Layout::load(name).unwrap();
+            assert_eq!(layout.name(), name);
+            assert_eq!(layout.rows().len(), 4);
+            assert!(Layout::new(name, layout.rows().to_vec()).is_ok());
+        }
+        let dvorak = Layout::built_in("dvorak").unwrap();
+        assert_eq!(dvorak.position('A'), Some((2, 0)));
+        assert_eq!(dvorak.finger('u'), Some(Finger::LeftIndex));
+        assert_eq!(dvorak.finger('d'), Some(Finger::RightIndex));
+        assert_eq!(dvorak.finger('='), Some(Finger::RightPinky));
+        assert_eq!(dvorak.finger('?'), None);
+        assert_eq!(Finger::LeftRing.to_string(), "left ring");
+        assert_eq!(Finger::RightRing.hand(), Hand::Right);
+        assert_eq!(dvorak.position('?'), None);
+        assert!(matches!(
+            Layout::load("azerty"),
+            Err(LayoutError::Unknown(_))
+        ));
+        assert!(matches!(
+            Layout::new("broken", vec!["abca".to_string()]),
+            Err(LayoutError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_keymap() {
+        let qwerty = Layout::default();
+        let colemak = Layout::built_in("colemak").unwrap();
+        let keymap = Keymap::between(&qwerty, &colemak);
+        let typed: String = "Hello, jk!".chars().map(|c| keymap.map(c)).collect();
+        assert_eq!(typed, "Hfiiy, ne!");
+        assert_eq!(Keymap::default().map('x'), 'x');
+    }
+
+    #[test]
+    fn test_key_stats() {
+        let mut stats = KeyStats::new();
+        assert!(stats.is_empty());
+        stats.record('a', true, None);
+        stats.record('A', false, Some(Duration::from_millis(300)));
+        stats.record('a', true, Some(Duration::from_millis(100)));
+        let stat = stats.get('a').unwrap();
+        assert_eq!(stat.presses, 3);
+        assert_eq!(stat.error_rate(), Some(1f32 / 3f32));
+        assert_eq!(stat.average_latency(), Some(Duration::from_millis(200)));
+        assert_eq!(stats.get('A'), None);
+
+        let mut merged = KeyStats::new();
+        merged.record('b', false, None);
+        merged.merge(&stats);
+        merged.merge(&stats);
+        assert_eq!(merged.get('a').unwrap().presses, 6);
+        assert_eq!(merged.get('b').unwrap().error_rate(), Some(1f32));
+
+        let json = serde_json::to_string(&stats).unwrap();
+        assert_eq!(
+            json,
+            "{\"a\":{\"presses\":3,\"errors\":1,\"latency_ms\":400,\"timed\":2}}"
+        );
+        assert_eq!(serde_json::from_str::<KeyStats>(&json).unwrap(), stats);
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+
+use regex::Regex;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Default)]
+pub struct LexiconFilter {
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    alphabet: Option<HashSet<char>>,
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+    stop_words: HashSet<String>,
+}
+
+impl LexiconFilter {
+    pub fn new() -> LexiconFilter {
+        LexiconFilter::default()
+    }
+
+    pub fn with_min_length(mut self, min_length: usize) -> LexiconFilter {
+        self.min_length = Some(min_length);
+        self
+    }
+
+    pub fn with_max_length(mut self, max_length: usize) -> LexiconFilter {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    pub fn with_alphabet(mut self, alphabet: &str) -> LexiconFilter {
+        self.alphabet = Some(alphabet.chars().collect());
+        self
+    }
+
+    pub fn with_include(mut self, include: Regex) -> LexiconFilter {
+        self.include = Some(include);
+        self
+    }
+
+    pub fn with_exclude(mut self, exclude: Regex) -> LexiconFilter {
+        self.exclude = Some(exclude);
+        self
+    }
+
+    pub fn with_stop_words<I>(mut self, stop_words: I) -> LexiconFilter
+    where
+        I: IntoIterator<Item = String>,
+    {
+        self.stop_words.extend(stop_words);
+        self
+    }
+
+    pub fn accepts(&self, word: &str) -> bool {
+        let length = word.graphemes(true).count();
+        self.min_length.is_none_or(|min| length >= min)
+            && self.max_length.is_none_or(|max| length <= max)
+            && self
+                .alphabet
+                .as_ref()
+                .is_none_or(|alphabet| word.chars().all(|c| alphabet.contains(&c)))
+            && self.include.as_ref().is_none_or(|re| re.is_match(word))
+            && !self.exclude.as_ref().is_some_and(|re| re.is_match(word))
+            && !self.stop_words.contains(word)
+    }
+}
+
+/// Reads one word per line, dropping blank lines and words rejected by
+/// `filter`. Fails if fewer than `min_size` words remain, since sampling
+/// batches of `min_size` from a smaller lexicon would silently come up short.
+pub fn load_lexicon<R: BufRead>(
+    reader: &mut R,
+    filter: &LexiconFilter,
+    min_size: usize,
+) -> Result<Vec<String>, LexiconError> {
+    let mut lexicon = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let word = line.trim();
+        if !word.is_empty() && filter.accepts(word) {
+            lexicon.push(word.to_owned());
+        }
+    }
+    if lexicon.len() < min_size {
+        return Err(LexiconError::TooSmall {
+            found: lexicon.len(),
+            required: min_size,
+        });
+    }
+    Ok(lexicon)
+}
+
+pub fn read_words<R: BufRead>(reader: &mut R) -> io::Result<Vec<String>> {
+    let mut words = Vec::new();
+    for line in reader.lines() {
+        words.extend(line?.split_whitespace().map(String::from));
+    }
+    Ok(words)
+}
+
+#[derive(Debug)]
+pub enum LexiconError {
+    Io(io::Error),
+    TooSmall { found: usize, required: usize },
+}
+
+impl fmt::Display for LexiconError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexiconError::Io(err) => write!(f, "Could not read lexicon: {}", err),
+            LexiconError::TooSmall { found, required } => write!(
+                f,
+                "The filtered lexicon has {} words but at least {} are required.",
+                found, required
+            ),
+        }
+    }
+}
+
+impl error::Error for LexiconError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LexiconError::Io(err) => Some(err),
+            LexiconError::TooSmall { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for LexiconError {
+    fn from(err: io::Error) -> LexiconError {
+        LexiconError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &str = "sphinx\nof\n\nblack\nquartz\njudge\nmy\nvow\n";
+
+    #[test]
+    fn test_unfiltered() {
+        let lexicon = load_lexicon(&mut WORDS.as_bytes(), &LexiconFilter::new(), 0).unwrap();
+        assert_eq!(
+            lexicon,
+            vec!["sphinx", "of", "black", "quartz", "judge", "my", "vow"]
+        );
+    }
+
+    #[test]
+    fn test_length_and_alphabet() {
+        let filter = LexiconFilter::new()
+            .with_min_length(2)
+            .with_max_length(5)
+            .with_alphabet("abcdefghijklmnopqrstuvw");
+        let lexicon = load_lexicon(&mut WORDS.as_bytes(), &filter, 0).unwrap();
+        assert_eq!(lexicon, vec!["of", "black", "judge", "vow"]);
+    }
+
+    #[test]
+    fn test_length_counts_graphemes() {
+        let words = "café\ncafe\u{301}\nпривет\n日本語\n";
+        let filter = LexiconFilter::new().with_max_length(4);
+        let lexicon = load_lexicon(&mut words.as_bytes(), &filter, 0).unwrap();
+        assert_eq!(lexicon, vec!["café", "cafe\u{301}", "日本語"]);
+    }
+
+    #[test]
+    fn test_regex_and_stop_words() {
+        let filter = LexiconFilter::new()
+            .with_include(Regex::new("[aeiou]").unwrap())
+            .with_exclude(Regex::new("^j").unwrap())
+            .with_stop_words(vec!["of".to_string()]);
+        let lexicon = load_lexicon(&mut WORDS.as_bytes(), &filter, 0).unwrap();
+        assert_eq!(lexicon, vec!["sphinx", "black", "quartz", "vow"]);
+    }
+
+    #[test]
+    fn test_too_small() {
+        let filter = LexiconFilter::new().with_min_length(6);
+        match load_lexicon(&mut WORDS.as_bytes(), &filter, 3) {
+            Err(LexiconError::TooSmall { found, required }) => {
+                assert_eq!(found, 2);
+                assert_eq!(required, 3);
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
@@ -1,9 +1,21 @@
 #[macro_use]
 extern crate lazy_static;
 
+pub mod compare;
+pub mod config;
 pub mod consts;
 pub mod controller;
+pub mod history;
+pub mod import;
 pub mod iter;
+pub mod keyboard;
+pub mod lexicon;
 pub mod model;
+pub mod net;
 pub mod performance;
+pub mod race;
+pub mod script;
+pub mod session;
+pub mod source;
+pub mod telemetry;
 pub mod view;
@@ -1,102 +1,1272 @@
-use rand;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::env;
+use std::error::Error;
+use std::fs;
 use std::fs::File;
 use std::io;
-use std::io::{BufRead, BufReader};
+use std::io::{BufReader, IsTerminal};
 use std::iter;
-use std::sync::Arc;
-use std::sync::RwLock;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::process;
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
+use clap::{App, Arg, ArgMatches, SubCommand};
+use cursive::event::Key;
+use cursive::views::{IdView, StackView};
 use cursive::Cursive;
+use regex::Regex;
 
+use fastfingers::compare::Comparison;
+use fastfingers::config::Config;
 use fastfingers::consts;
 use fastfingers::controller;
+use fastfingers::history;
+use fastfingers::history::{History, PersonalBests, Record, Variant, WordTime};
+use fastfingers::import;
+use fastfingers::keyboard::{KeyStats, Layout};
+use fastfingers::lexicon;
 use fastfingers::model::{Model, ModelBuilder};
+use fastfingers::net::{Client, Host, Message, WordSettings};
 use fastfingers::performance::PerformanceMonitor;
+use fastfingers::race;
+use fastfingers::race::{Ghost, Outcome, Pacer, Scoreboard, MAX_PACER_WPM};
+use fastfingers::script::Script;
+use fastfingers::session::Session;
+use fastfingers::source::{IterSource, MarkovChain, Punctuate, Recorder, WordSource};
+use fastfingers::telemetry::{Event, Telemetry};
 use fastfingers::view;
-use fastfingers::view::ViewBuilder;
+use fastfingers::view::{get_heatmap, Metric, Settings, ViewBuilder};
 
-fn get_lexicon<R: BufRead>(reader: &mut R) -> Vec<String> {
-    reader.lines().filter_map(Result::ok).collect()
+fn is_usize(value: String) -> Result<(), String> {
+    value
+        .parse::<usize>()
+        .map(|_| ())
+        .map_err(|err| err.to_string())
 }
 
-fn main() -> io::Result<()> {
-    let file = File::open(consts::INPUT_FILE)?;
+fn is_u64(value: String) -> Result<(), String> {
+    value
+        .parse::<u64>()
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+fn is_u16(value: String) -> Result<(), String> {
+    value
+        .parse::<u16>()
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+fn is_positive_f64(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(value) if value > 0.0 => Ok(()),
+        Ok(_) => Err("must be positive".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+/// Parses a pace of `WPM` or `MIN-MAX` words per minute.
+fn parse_pace(value: &str) -> Result<(f64, f64), String> {
+    let parse = |wpm: &str| match wpm.parse::<f64>() {
+        Ok(wpm) if wpm > MAX_PACER_WPM => Err(format!("must be at most {}", MAX_PACER_WPM)),
+        Ok(wpm) if wpm > 0.0 => Ok(wpm),
+        Ok(_) => Err("must be positive".to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+    let (min_wpm, max_wpm) = match value.split_once('-') {
+        Some((min_wpm, max_wpm)) => (parse(min_wpm)?, parse(max_wpm)?),
+        None => (parse(value)?, parse(value)?),
+    };
+    if min_wpm > max_wpm {
+        return Err("the minimum must not exceed the maximum".to_string());
+    }
+    Ok((min_wpm, max_wpm))
+}
+
+fn is_pace(value: String) -> Result<(), String> {
+    parse_pace(&value).map(|_| ())
+}
+
+fn is_regex(value: String) -> Result<(), String> {
+    Regex::new(&value)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+fn name_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("name")
+        .long("name")
+        .value_name("NAME")
+        .help("Name shown to other players, defaults to $USER")
+}
+
+fn get_matches<'a>() -> ArgMatches<'a> {
+    App::new(consts::FAST_FINGERS)
+        .about("A typing test.")
+        .arg(
+            Arg::with_name("lexicon")
+                .global(true)
+                .long("lexicon")
+                .value_name("FILE")
+                .help("Word list to sample from, one word per line"),
+        )
+        .arg(
+            Arg::with_name("min-length")
+                .global(true)
+                .long("min-length")
+                .value_name("N")
+                .validator(is_usize)
+                .help("Excludes words with fewer than N characters"),
+        )
+        .arg(
+            Arg::with_name("max-length")
+                .global(true)
+                .long("max-length")
+                .value_name("N")
+                .validator(is_usize)
+                .help("Excludes words with more than N characters"),
+        )
+        .arg(
+            Arg::with_name("alphabet")
+                .global(true)
+                .long("alphabet")
+                .value_name("CHARS")
+                .help("Only keeps words made entirely of these characters"),
+        )
+        .arg(
+            Arg::with_name("include")
+                .global(true)
+                .long("include")
+                .value_name("REGEX")
+                .validator(is_regex)
+                .help("Only keeps words matching this pattern"),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .global(true)
+                .long("exclude")
+                .value_name("REGEX")
+                .validator(is_regex)
+                .help("Excludes words matching this pattern"),
+        )
+        .arg(
+            Arg::with_name("stop-words")
+                .global(true)
+                .long("stop-words")
+                .value_name("FILE")
+                .help("Excludes every word listed in this file"),
+        )
+        .arg(
+            Arg::with_name("punctuation")
+                .global(true)
+                .long("punctuation")
+                .help("Decorates words with punctuation and capitalization"),
+        )
+        .arg(
+            Arg::with_name("no-punctuation")
+                .global(true)
+                .long("no-punctuation")
+                .overrides_with("punctuation")
+                .help("Turns off --punctuation set in the config file"),
+        )
+        .arg(
+            Arg::with_name("numbers")
+                .global(true)
+                .long("numbers")
+                .help("Occasionally inserts numbers between words"),
+        )
+        .arg(
+            Arg::with_name("no-numbers")
+                .global(true)
+                .long("no-numbers")
+                .overrides_with("numbers")
+                .help("Turns off --numbers set in the config file"),
+        )
+        .arg(
+            Arg::with_name("corpus")
+                .global(true)
+                .long("corpus")
+                .value_name("FILE")
+                .help(
+                    "Generates sentences from a Markov model of this text instead of the lexicon",
+                ),
+        )
+        .arg(
+            Arg::with_name("order")
+                .global(true)
+                .long("order")
+                .value_name("N")
+                .default_value("1")
+                .validator(is_usize)
+                .help("Words of context used by the Markov model: 1 for bigrams, 2 for trigrams"),
+        )
+        .arg(
+            Arg::with_name("ignore-case")
+                .global(true)
+                .long("ignore-case")
+                .help("Accepts words typed with different capitalization"),
+        )
+        .arg(
+            Arg::with_name("no-ignore-case")
+                .global(true)
+                .long("no-ignore-case")
+                .overrides_with("ignore-case")
+                .help("Turns off --ignore-case set in the config file"),
+        )
+        .arg(
+            Arg::with_name("ignore-accents")
+                .global(true)
+                .long("ignore-accents")
+                .help("Accepts words typed without accents, e.g. \"ecole\" for \"école\""),
+        )
+        .arg(
+            Arg::with_name("no-ignore-accents")
+                .global(true)
+                .long("no-ignore-accents")
+                .overrides_with("ignore-accents")
+                .help("Turns off --ignore-accents set in the config file"),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .value_name("N")
+                .global(true)
+                .validator(is_u64)
+                .help("Seeds the word sequence so that it can be reproduced"),
+        )
+        .arg(
+            Arg::with_name("ghost")
+                .long("ghost")
+                .value_name("FILE")
+                .help("Races against a run recorded with --record"),
+        )
+        .arg(
+            Arg::with_name("auto-pause")
+                .long("auto-pause")
+                .value_name("SECS")
+                .validator(is_u64)
+                .help("Pauses after this many seconds without typing, or never if 0"),
+        )
+        .arg(
+            Arg::with_name("config")
+                .global(true)
+                .long("config")
+                .value_name("FILE")
+                .help("Reads settings from this file instead of the default config file"),
+        )
+        .arg(
+            Arg::with_name("theme")
+                .global(true)
+                .long("theme")
+                .value_name("NAME")
+                .help(
+                    "Colors the interface with a built-in theme (default, colorblind, dark, mono) \
+                     or a theme file",
+                ),
+        )
+        .arg(
+            Arg::with_name("layout")
+                .global(true)
+                .long("layout")
+                .value_name("NAME")
+                .help(
+                    "The keyboard layout typed with (qwerty, dvorak, colemak, workman) \
+                     or a file with a row of keys per line",
+                ),
+        )
+        .arg(
+            Arg::with_name("simulate")
+                .global(true)
+                .long("simulate")
+                .value_name("NAME")
+                .help("Remaps the keys of --layout to practice this layout without switching"),
+        )
+        .arg(
+            Arg::with_name("no-history")
+                .global(true)
+                .long("no-history")
+                .help("Does not save the session to the history file"),
+        )
+        .arg(
+            Arg::with_name("telemetry")
+                .global(true)
+                .long("telemetry")
+                .value_name("PORT")
+                .validator(is_u16)
+                .help(
+                    "Streams live session events as JSON lines to clients of this localhost port",
+                ),
+        )
+        .arg(
+            Arg::with_name("pace")
+                .long("pace")
+                .value_name("WPM")
+                .validator(is_pace)
+                .help("Races against a bot typing at this speed, or at a speed varying in MIN-MAX"),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .value_name("FILE")
+                .help("Saves every keystroke to a replay file when the session ends"),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Plays back a recorded session")
+                .arg(
+                    Arg::with_name("FILE")
+                        .required(true)
+                        .help("A file written with --record"),
+                )
+                .arg(
+                    Arg::with_name("speed")
+                        .long("speed")
+                        .value_name("FACTOR")
+                        .default_value("1")
+                        .validator(is_positive_f64)
+                        .help("Plays back faster or slower than real time"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("host")
+                .about("Hosts a race for other players on the network")
+                .arg(
+                    Arg::with_name("address")
+                        .long("address")
+                        .value_name("ADDRESS")
+                        .default_value(consts::HOST_ADDRESS)
+                        .help("Address to accept players on"),
+                )
+                .arg(name_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("join")
+                .about("Joins a race hosted with `host`")
+                .arg(
+                    Arg::with_name("ADDRESS")
+                        .required(true)
+                        .help("The host's address, e.g. 192.168.1.10:7878"),
+                )
+                .arg(name_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("stats")
+                .about("Summarizes the sessions saved in the history file")
+                .arg(
+                    Arg::with_name("count")
+                        .long("count")
+                        .value_name("N")
+                        .default_value("10")
+                        .validator(is_u64)
+                        .help("Lists this many of the slowest words"),
+                )
+                .arg(
+                    Arg::with_name("heatmap")
+                        .long("heatmap")
+                        .value_name("METRIC")
+                        .possible_values(&["errors", "latency"])
+                        .help("Draws a keyboard shaded by the error rate or latency of each key"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Writes the sessions saved in the history file as CSV or JSON")
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .possible_values(&["csv", "json"])
+                        .default_value("csv")
+                        .help("The format to write"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .value_name("FILE")
+                        .help("Writes to this file instead of the standard output"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Adds the results in a CSV exported by Monkeytype or fastfinge-rs to the history")
+                .arg(
+                    Arg::with_name("FILE")
+                        .required(true)
+                        .index(1)
+                        .help("The CSV file to import"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("config")
+                .about("Shows where settings are read from")
+                .arg(
+                    Arg::with_name("print-default")
+                        .long("print-default")
+                        .help("Prints the effective settings as a config file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("script")
+                .about("Replays a keystroke script without a terminal and prints the results")
+                .arg(
+                    Arg::with_name("FILE")
+                        .required(true)
+                        .help("Lines of `<milliseconds> <character|space|backspace>`"),
+                ),
+        )
+        .get_matches()
+}
+
+fn file_stem(path: &str) -> String {
+    Path::new(path).file_stem().map_or_else(
+        || path.to_owned(),
+        |stem| stem.to_string_lossy().into_owned(),
+    )
+}
+
+type WordStream = Box<dyn Iterator<Item = String> + Send + Sync>;
+
+fn get_lexicon_stream(config: &Config, rng: StdRng) -> Result<WordStream, Box<dyn Error>> {
+    let filter = config.lexicon_filter()?;
+    let file = File::open(&config.lexicon)?;
     let mut reader = BufReader::new(file);
 
-    let lexicon = get_lexicon(&mut reader);
-    let mut rng = rand::thread_rng();
+    let sample_size = config.sample_size;
+    let lexicon = lexicon::load_lexicon(&mut reader, &filter, sample_size)?;
+    let mut rng = rng;
     let word_stream = iter::repeat_with(move || {
         lexicon
-            .choose_multiple(&mut rng, consts::SAMPLE_SIZE)
+            .choose_multiple(&mut rng, sample_size)
             .cloned()
             .collect::<Vec<String>>()
     })
     .flatten();
-    let model: Model<_> = ModelBuilder::new().with_word_stream(word_stream).build();
-    let model_arc = Arc::new(RwLock::new(model));
-    let model_on_edit_instance = model_arc.clone();
-    let model_on_start_instance = model_arc.clone();
+    Ok(Box::new(word_stream))
+}
 
-    let performance = PerformanceMonitor::new();
-    let performance_arc = Arc::new(RwLock::new(performance));
-    let performance_on_edit_instance = performance_arc.clone();
-    let performance_on_start_instance = performance_arc.clone();
-    let performance_background_instance = performance_arc.clone();
+fn get_markov_stream(
+    matches: &ArgMatches,
+    corpus: &str,
+    rng: StdRng,
+) -> Result<WordStream, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(corpus)?);
+    let corpus = lexicon::read_words(&mut reader)?;
+    let order = matches.value_of("order").unwrap().parse()?;
+    let chain = MarkovChain::new(&corpus, order)?;
+    Ok(Box::new(chain.into_words(rng)))
+}
 
-    let view = ViewBuilder::new()
-        .with_initial_words(&model_arc.clone().read().unwrap().get_words())
-        .with_edit_callback(move |siv: &mut Cursive, contents, _cursor| {
-            controller::on_keypress(
-                siv,
-                &mut model_on_edit_instance.write().unwrap(),
-                &mut performance_on_edit_instance.write().unwrap(),
-                contents,
-                _cursor,
+fn get_seed(matches: &ArgMatches) -> Result<u64, Box<dyn Error>> {
+    match matches.value_of("seed") {
+        Some(seed) => Ok(seed.parse()?),
+        None => Ok(rand::random()),
+    }
+}
+
+fn read_script(path: &str) -> Result<Script, Box<dyn Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(Script::parse(&mut reader)?)
+}
+
+/// Serves the ghost's words first so both race on the same sequence, then
+/// carries on with `source` once the user gets past them.
+fn get_ghost_source(ghost: &Script, mut source: Box<dyn WordSource>) -> Box<dyn WordSource> {
+    let name = source.name().to_owned();
+    let words = ghost.words().to_vec();
+    for _ in 0..words.len() {
+        source.next_word();
+    }
+    let word_stream = words
+        .into_iter()
+        .chain(iter::from_fn(move || source.next_word()));
+    Box::new(IterSource::new(word_stream, &name))
+}
+
+fn get_source(
+    matches: &ArgMatches,
+    config: &Config,
+    seed: u64,
+) -> Result<Box<dyn WordSource>, Box<dyn Error>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let stream_rng = StdRng::seed_from_u64(rng.gen());
+    let (word_stream, name) = match matches.value_of("corpus") {
+        Some(corpus) => (
+            get_markov_stream(matches, corpus, stream_rng)?,
+            format!("markov:{}", file_stem(corpus)),
+        ),
+        None => (
+            get_lexicon_stream(config, stream_rng)?,
+            file_stem(&config.lexicon),
+        ),
+    };
+    let word_stream = Punctuate::new(word_stream, StdRng::seed_from_u64(rng.gen()))
+        .with_punctuation(config.punctuation)
+        .with_numbers(config.numbers);
+    Ok(Box::new(IterSource::new(word_stream, &name)))
+}
+
+/// How `get_source` draws words, as far as it sets sessions apart for
+/// personal bests. Filters only apply to lexicons.
+fn get_variant(matches: &ArgMatches, config: &Config) -> Variant {
+    let filters = match matches.value_of("corpus") {
+        Some(_) => String::new(),
+        None => config.filter_summary(),
+    };
+    Variant {
+        punctuation: config.punctuation,
+        numbers: config.numbers,
+        filters,
+        ..Variant::default()
+    }
+}
+
+/// Everything besides the seed that `get_source` draws words by, which the
+/// players of a race must share with the host.
+fn get_word_settings(
+    matches: &ArgMatches,
+    config: &Config,
+    source: &str,
+) -> Result<WordSettings, Box<dyn Error>> {
+    let mut stop_words = match &config.stop_words {
+        Some(path) => lexicon::read_words(&mut BufReader::new(File::open(path)?))?,
+        None => Vec::new(),
+    };
+    stop_words.sort();
+    stop_words.dedup();
+    let order = match matches.value_of("corpus") {
+        Some(_) => Some(matches.value_of("order").unwrap().parse()?),
+        None => None,
+    };
+    Ok(WordSettings {
+        source: source.to_owned(),
+        sample_size: config.sample_size,
+        punctuation: config.punctuation,
+        numbers: config.numbers,
+        order,
+        min_length: config.min_length,
+        max_length: config.max_length,
+        alphabet: config.alphabet.clone(),
+        include: config.include.clone(),
+        exclude: config.exclude.clone(),
+        stop_words,
+    })
+}
+
+/// Reads the config file, then applies any flags given on the command line.
+fn get_config(matches: &ArgMatches) -> Result<Config, Box<dyn Error>> {
+    let mut config = match matches.value_of("config") {
+        Some(path) => Config::load(Path::new(path))?,
+        None => match Config::default_path() {
+            Some(path) if path.exists() => Config::load(&path)?,
+            _ => Config::default(),
+        },
+    };
+    if let Some(lexicon) = matches.value_of("lexicon") {
+        config.lexicon = lexicon.to_owned();
+    }
+    if let Some(layout) = matches.value_of("layout") {
+        config.layout = layout.to_owned();
+    }
+    if let Some(simulate) = matches.value_of("simulate") {
+        config.simulate = Some(simulate.to_owned());
+    }
+    config.keymap()?;
+    if let Some(theme) = matches.value_of("theme") {
+        config.theme = theme.to_owned();
+        config.theme()?;
+    }
+    if let Some(secs) = matches.value_of("auto-pause") {
+        config.auto_pause_secs = secs.parse()?;
+    }
+    if let Some(min_length) = matches.value_of("min-length") {
+        config.min_length = Some(min_length.parse()?);
+    }
+    if let Some(max_length) = matches.value_of("max-length") {
+        config.max_length = Some(max_length.parse()?);
+    }
+    for (name, setting) in [
+        ("alphabet", &mut config.alphabet),
+        ("include", &mut config.include),
+        ("exclude", &mut config.exclude),
+        ("stop-words", &mut config.stop_words),
+    ]
+    .iter_mut()
+    {
+        if let Some(value) = matches.value_of(name) {
+            **setting = Some(value.to_owned());
+        }
+    }
+    for (name, setting) in [
+        ("punctuation", &mut config.punctuation),
+        ("numbers", &mut config.numbers),
+        ("ignore-case", &mut config.ignore_case),
+        ("ignore-accents", &mut config.ignore_accents),
+    ]
+    .iter_mut()
+    {
+        if matches.is_present(*name) {
+            **setting = true;
+        } else if matches.is_present(format!("no-{}", name)) {
+            **setting = false;
+        }
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+fn get_model_builder(config: &Config) -> ModelBuilder {
+    ModelBuilder::new().with_panel_size(config.panel_cols, config.panel_rows)
+}
+
+fn get_performance_monitor(config: &Config) -> PerformanceMonitor {
+    let comparison = Comparison::new()
+        .with_ignore_case(config.ignore_case)
+        .with_ignore_accents(config.ignore_accents);
+    PerformanceMonitor::new().with_comparison(comparison)
+}
+
+fn run_config(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let config = get_config(matches)?;
+    if matches.is_present("print-default") {
+        print!("{}", config);
+        return Ok(());
+    }
+    match (matches.value_of("config"), Config::default_path()) {
+        (Some(path), _) => println!("{}", path),
+        (None, Some(path)) if path.exists() => println!("{}", path.display()),
+        (None, Some(path)) => println!("{} (not found, using defaults)", path.display()),
+        (None, None) => println!("No config directory, using defaults"),
+    }
+    Ok(())
+}
+
+fn run_script(matches: &ArgMatches, path: &str) -> Result<(), Box<dyn Error>> {
+    let config = get_config(matches)?;
+    let script = read_script(path)?;
+    let source: Box<dyn WordSource> = if script.words().is_empty() {
+        get_source(matches, &config, get_seed(matches)?)?
+    } else {
+        let words = script.words().to_vec();
+        let total_words = words.len();
+        Box::new(IterSource::new(words.into_iter(), "script").with_total_words(total_words))
+    };
+    let model = get_model_builder(&config).with_source(source).build();
+    let mut session = Session::new(model, get_performance_monitor(&config));
+    script.run(&mut session, Instant::now())?;
+    println!("{}", session.performance());
+    Ok(())
+}
+
+fn get_telemetry(matches: &ArgMatches) -> Result<Option<Telemetry>, Box<dyn Error>> {
+    match matches.value_of("telemetry") {
+        Some(port) => Ok(Some(Telemetry::bind((Ipv4Addr::LOCALHOST, port.parse()?))?)),
+        None => Ok(None),
+    }
+}
+
+/// Publishes every submitted word, and a performance snapshot every
+/// `TELEMETRY_INTERVAL_MS` until the process exits.
+fn spawn_telemetry(telemetry: Telemetry, session_arc: Arc<RwLock<Session>>) {
+    session_arc
+        .write()
+        .unwrap()
+        .add_observer(Box::new(telemetry.clone()));
+    thread::spawn(move || loop {
+        thread::sleep(Duration::from_millis(consts::TELEMETRY_INTERVAL_MS));
+        let event = {
+            let session = session_arc.read().unwrap();
+            if !session.performance().is_started() {
+                continue;
+            }
+            Event::performance(session.performance())
+        };
+        telemetry.publish(event);
+    });
+}
+
+/// Runs the interface until the user quits, then ends the session.
+fn run_cursive(
+    mut siv: Cursive,
+    view: IdView<StackView>,
+    session_arc: Arc<RwLock<Session>>,
+    telemetry: Option<Telemetry>,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let theme = config.theme()?;
+    siv.set_theme(theme.cursive);
+    siv.set_user_data(Settings {
+        panel_cols: config.panel_cols,
+        styles: theme.styles,
+    });
+    let refresh = Duration::from_millis(config.performance_refresh_ms);
+    if let Some(telemetry) = telemetry.clone() {
+        spawn_telemetry(telemetry, session_arc.clone());
+    }
+    let session_tick_instance = session_arc.clone();
+    let cb_sink = siv.cb_sink().clone();
+    thread::spawn(move || loop {
+        let session_iteration_instance = session_tick_instance.clone();
+        cb_sink
+            .send(Box::new(move |siv: &mut Cursive| {
+                controller::on_tick(siv, &mut session_iteration_instance.write().unwrap());
+            }))
+            .unwrap();
+        thread::sleep(refresh);
+    });
+
+    siv.add_layer(view);
+    siv.run();
+
+    let event = {
+        let mut session = session_arc.write().unwrap();
+        end_session(&mut session);
+        Event::performance(session.performance())
+    };
+    if let Some(telemetry) = telemetry {
+        telemetry.publish(event);
+        telemetry.flush();
+    }
+    Ok(())
+}
+
+fn end_session(session: &mut Session) {
+    if !session.performance().is_ended() {
+        session
+            .end(session.now())
+            .expect("The performance monitor should not have been ended yet.");
+    }
+}
+
+fn print_results(session: &Session, layout: &Layout) {
+    println!("{}", session.performance());
+    if let Some(personal_bests) = session.personal_bests() {
+        print_personal_best(session, personal_bests);
+    }
+    let samples = session.performance().samples();
+    if !samples.is_empty() {
+        println!(
+            "\n{}\n",
+            view::get_chart(&samples, consts::GRAPH_COLS, consts::GRAPH_ROWS)
+        );
+    }
+    let snapshot = session.snapshot(session.now());
+    let progress = snapshot.progress();
+    let result = |position| {
+        format!(
+            "{}, {}",
+            race::standing(progress, position),
+            Outcome::of(progress, position)
+        )
+    };
+    if let Some(ghost) = snapshot.ghost {
+        println!("Ghost: {}", result(ghost));
+    }
+    if let (Some(pacer), Some(position)) = (session.pacer(), snapshot.pacer) {
+        println!("Pacer ({}): {}", pacer, result(position));
+    }
+    for (name, player) in snapshot.players.iter() {
+        println!("{}: {}", name, result(player.words));
+    }
+    let words: Vec<WordTime> = session.submissions().iter().map(WordTime::from).collect();
+    let slowest = history::slowest_words(&words, consts::SLOWEST_WORDS);
+    if !slowest.is_empty() {
+        let slowest: Vec<String> = slowest
+            .iter()
+            .map(|(word, duration, _)| format!("{} ({:.2}s)", word, duration.as_secs_f32()))
+            .collect();
+        println!("Slowest words: {}", slowest.join(", "));
+    }
+    if !session.keys().is_empty() {
+        for metric in [Metric::Errors, Metric::Latency].iter() {
+            println!(
+                "\n{}",
+                get_heatmap(session.keys(), layout, *metric, is_color())
             );
+        }
+        println!("\n{}\n", session.performance().finger_stats(layout));
+    }
+}
+
+/// Compares the session with the fastest earlier one of its category.
+fn print_personal_best(session: &Session, personal_bests: &PersonalBests) {
+    if session.submissions().is_empty() {
+        return;
+    }
+    let summary = session.performance().summary();
+    let category = session.category(summary.duration());
+    match personal_bests.get(&category) {
+        None => println!(
+            "New personal best ({}): {:.1} WPM, the first session of its kind",
+            category, summary.wpm
+        ),
+        Some(best) if summary.wpm > best.wpm => println!(
+            "New personal best ({}): {:.1} WPM, {:+.1} over {:.1}",
+            category,
+            summary.wpm,
+            summary.wpm - best.wpm,
+            best.wpm
+        ),
+        Some(best) => println!(
+            "Personal best ({}): {:.1} WPM ({:+.1})",
+            category,
+            best.wpm,
+            summary.wpm - best.wpm
+        ),
+    }
+}
+
+/// The fastest sessions in the history, or none if there is nowhere to
+/// keep it.
+fn get_personal_bests() -> Result<PersonalBests, Box<dyn Error>> {
+    match History::default_path() {
+        Some(path) => Ok(PersonalBests::new(&History::new(&path).records()?)),
+        None => Ok(PersonalBests::default()),
+    }
+}
+
+/// Whether results may be coloured with terminal escape codes.
+fn is_color() -> bool {
+    io::stdout().is_terminal()
+}
+
+fn get_history() -> Result<History, Box<dyn Error>> {
+    match History::default_path() {
+        Some(path) => Ok(History::new(&path)),
+        None => Err("No data directory to keep the history in.".into()),
+    }
+}
+
+/// Appends the session to the history unless disabled or nothing was typed.
+fn save_history(matches: &ArgMatches, session: &Session, seed: u64) -> Result<(), Box<dyn Error>> {
+    if matches.is_present("no-history") || session.submissions().is_empty() {
+        return Ok(());
+    }
+    let record = Record::new(session, SystemTime::now()).with_seed(seed);
+    get_history()?.append(&record)?;
+    Ok(())
+}
+
+fn run_export(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let records = get_history()?.records()?;
+    let text = match matches.value_of("format") {
+        Some("json") => history::to_json(&records)?,
+        _ => history::to_csv(&records),
+    };
+    match matches.value_of("output") {
+        Some(path) => fs::write(path, text)?,
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// Merges the records of a result file into the history, skipping those
+/// already in it.
+fn run_import(path: &str) -> Result<(), Box<dyn Error>> {
+    let (format, imported) = import::import(&fs::read_to_string(path)?)?;
+    let history = get_history()?;
+    let mut records = history.records()?;
+    let total = imported.len();
+    let added = import::merge(&mut records, imported);
+    records.sort_by_key(|record| record.timestamp);
+    history.save(&records)?;
+    println!("Imported {} of {} records from {}", added, total, format);
+    Ok(())
+}
+
+fn run_stats(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let config = get_config(matches)?;
+    let history = get_history()?;
+    let records = history.records()?;
+    println!(
+        "History: {} ({} sessions)",
+        history.path().display(),
+        records.len()
+    );
+    if records.is_empty() {
+        return Ok(());
+    }
+    let wpm = records.iter().map(|record| record.summary.wpm);
+    let best = wpm.clone().fold(0f32, f32::max);
+    let average = wpm.sum::<f32>() / records.len() as f32;
+    println!("Best WPM: {:.1}\nAverage WPM: {:.1}", best, average);
+    if let Some(metric) = matches.value_of("heatmap") {
+        let mut keys = KeyStats::new();
+        for record in records.iter() {
+            keys.merge(&record.keys);
+        }
+        println!(
+            "{}",
+            get_heatmap(&keys, &config.layout()?, metric.parse()?, is_color())
+        );
+        return Ok(());
+    }
+    let count = matches.value_of("count").unwrap().parse()?;
+    let words = records.iter().flat_map(|record| record.words.iter());
+    println!("Slowest words:");
+    for (word, duration, times) in history::slowest_words(words, count) {
+        println!(
+            "  {:<16} {:.2}s ({} times)",
+            word,
+            duration.as_secs_f32(),
+            times
+        );
+    }
+    Ok(())
+}
+
+fn get_player_name(matches: &ArgMatches) -> String {
+    matches
+        .value_of("name")
+        .map(String::from)
+        .or_else(|| env::var("USER").ok())
+        .unwrap_or_else(|| consts::PLAYER.to_string())
+}
+
+fn get_progress_message(name: &str, session: &Session) -> Message {
+    let name = name.to_owned();
+    let words = session.snapshot(session.now()).progress();
+    let wpm = session.performance().wpm().unwrap_or_default();
+    if session.performance().is_ended() {
+        Message::Finish { name, words, wpm }
+    } else {
+        Message::Progress { name, words, wpm }
+    }
+}
+
+/// Reports the player's progress whenever a word is submitted or their
+/// rounded WPM changes.
+fn spawn_reporter<F>(session_arc: Arc<RwLock<Session>>, name: String, interval: Duration, report: F)
+where
+    F: Fn(&Message) + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut last_reported = None;
+        loop {
+            thread::sleep(interval);
+            let session = session_arc.read().unwrap();
+            if !session.performance().is_started() {
+                continue;
+            }
+            let message = get_progress_message(&name, &session);
+            if let Message::Progress { words, wpm, .. } = message {
+                let reported = Some((words, wpm.round() as i64));
+                if reported != last_reported {
+                    report(&message);
+                    last_reported = reported;
+                }
+            }
+        }
+    });
+}
+
+fn get_multiplayer_session(
+    matches: &ArgMatches,
+    config: &Config,
+    source: Box<dyn WordSource>,
+    scoreboard: Arc<Mutex<Scoreboard>>,
+) -> Result<Arc<RwLock<Session>>, Box<dyn Error>> {
+    let model = get_model_builder(config).with_source(source).build();
+    let session = get_session(matches, config, model)?.with_scoreboard(scoreboard);
+    Ok(Arc::new(RwLock::new(session)))
+}
+
+fn get_session(
+    matches: &ArgMatches,
+    config: &Config,
+    model: Model,
+) -> Result<Session, Box<dyn Error>> {
+    let session = Session::new(model, get_performance_monitor(config))
+        .with_variant(get_variant(matches, config));
+    match config.keymap()? {
+        Some(keymap) => Ok(session.with_keymap(keymap)),
+        None => Ok(session),
+    }
+}
+
+fn get_edit_callback(session_arc: Arc<RwLock<Session>>) -> impl FnMut(&mut Cursive, &str, usize) {
+    move |siv: &mut Cursive, contents, cursor| {
+        controller::on_keypress(siv, &mut session_arc.write().unwrap(), contents, cursor);
+    }
+}
+
+fn run_host(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let config = get_config(matches)?;
+    let telemetry = get_telemetry(matches)?;
+    let name = get_player_name(matches);
+    let seed = get_seed(matches)?;
+    let source = get_source(matches, &config, seed)?;
+    let address = matches.value_of("address").unwrap();
+    let settings = get_word_settings(matches, &config, source.name())?;
+    let host = Arc::new(Host::bind(address, &name, seed, settings)?);
+    host.serve()?;
+    let session_arc = get_multiplayer_session(matches, &config, source, host.scoreboard())?;
+    let session_on_start_instance = session_arc.clone();
+    let host_on_start_instance = host.clone();
+
+    let greeting = format!(
+        "Hosting on {}. Start once everyone has joined.",
+        host.local_addr()?
+    );
+    let view = ViewBuilder::new()
+        .with_panel_size(config.panel_cols, config.panel_rows)
+        .with_initial_words(&session_arc.read().unwrap().model().get_words())
+        .with_edit_callback(get_edit_callback(session_arc.clone()))
+        .with_start_callback(move |siv: &mut Cursive| {
+            host_on_start_instance.start();
+            controller::on_start(siv, &mut session_on_start_instance.write().unwrap());
+        })
+        .with_greeting(&greeting)
+        .with_race_panel()
+        .build();
+
+    let host_reporter_instance = host.clone();
+    let refresh = Duration::from_millis(config.performance_refresh_ms);
+    spawn_reporter(session_arc.clone(), name.clone(), refresh, move |message| {
+        host_reporter_instance.report(message)
+    });
+    run_cursive(
+        Cursive::default(),
+        view,
+        session_arc.clone(),
+        telemetry,
+        &config,
+    )?;
+
+    let session = session_arc.read().unwrap();
+    host.report(&get_progress_message(&name, &session));
+    print_results(&session, &config.layout()?);
+    save_history(matches, &session, seed)
+}
+
+fn run_join(matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let config = get_config(matches)?;
+    let telemetry = get_telemetry(matches)?;
+    let name = get_player_name(matches);
+    let mut client = Client::connect(matches.value_of("ADDRESS").unwrap(), &name)?;
+    let source = get_source(matches, &config, client.seed())?;
+    let settings = get_word_settings(matches, &config, source.name())?;
+    let differences = settings.differences(client.settings());
+    if !differences.is_empty() {
+        let message = format!(
+            "This player draws words differently from the host. Use the same {} as the host.",
+            differences.join(", ")
+        );
+        return Err(message.into());
+    }
+    let scoreboard = Arc::new(Mutex::new(Scoreboard::new()));
+    let session_arc = get_multiplayer_session(matches, &config, source, scoreboard.clone())?;
+    let session_on_start_instance = session_arc.clone();
+
+    let siv = Cursive::default();
+    let cb_sink = siv.cb_sink().clone();
+    client.listen(scoreboard, move || {
+        let _ = cb_sink.send(Box::new(move |siv: &mut Cursive| {
+            controller::on_start(siv, &mut session_on_start_instance.write().unwrap());
+        }));
+    });
+    let view = ViewBuilder::<_, fn(&mut Cursive)>::new()
+        .with_panel_size(config.panel_cols, config.panel_rows)
+        .with_initial_words(&session_arc.read().unwrap().model().get_words())
+        .with_edit_callback(get_edit_callback(session_arc.clone()))
+        .with_greeting("Waiting for the host to start.")
+        .with_race_panel()
+        .build();
+
+    let client = Arc::new(client);
+    let client_reporter_instance = client.clone();
+    let refresh = Duration::from_millis(config.performance_refresh_ms);
+    spawn_reporter(session_arc.clone(), name.clone(), refresh, move |message| {
+        let _ = client_reporter_instance.report(message);
+    });
+    run_cursive(siv, view, session_arc.clone(), telemetry, &config)?;
+
+    let session = session_arc.read().unwrap();
+    let _ = client.report(&get_progress_message(&name, &session));
+    print_results(&session, &config.layout()?);
+    save_history(matches, &session, client.seed())
+}
+
+fn run_replay(matches: &ArgMatches, path: &str) -> Result<(), Box<dyn Error>> {
+    let config = get_config(matches)?;
+    let telemetry = get_telemetry(matches)?;
+    let script = Arc::new(read_script(path)?);
+    let speed: f64 = matches.value_of("speed").unwrap().parse()?;
+    let words = script.words().to_vec();
+    let total_words = words.len();
+    let source = IterSource::new(words.into_iter(), "replay").with_total_words(total_words);
+    let model = get_model_builder(&config)
+        .with_source(Box::new(source))
+        .build();
+    let performance = get_performance_monitor(&config).with_speed(speed);
+    let session = Session::new(model, performance);
+    let session_arc = Arc::new(RwLock::new(session));
+    let session_on_edit_instance = session_arc.clone();
+    let session_on_start_instance = session_arc.clone();
+
+    let view = ViewBuilder::new()
+        .with_panel_size(config.panel_cols, config.panel_rows)
+        .with_initial_words(&session_arc.read().unwrap().model().get_words())
+        .with_edit_callback(move |siv: &mut Cursive, _contents, _cursor| {
+            controller::on_refresh(siv, &session_on_edit_instance.read().unwrap());
         })
         .with_start_callback(move |siv: &mut Cursive| {
-            controller::on_start(siv, &model_on_start_instance.read().unwrap());
-            performance_on_start_instance
-                .write()
-                .unwrap()
-                .start()
-                .expect("The performance monitor should not have been started yet.");
+            let start = {
+                let mut session = session_on_start_instance.write().unwrap();
+                controller::on_start(siv, &mut session);
+                session.performance().started_at().unwrap()
+            };
+            // The session runs in the time of the script, while keystrokes are
+            // played back `speed` times as fast.
+            let script = script.clone();
+            let session_playback_instance = session_on_start_instance.clone();
+            let cb_sink = siv.cb_sink().clone();
+            thread::spawn(move || {
+                for &(offset, keystroke) in script.events().iter() {
+                    let due = start + offset.div_f64(speed);
+                    thread::sleep(due.saturating_duration_since(Instant::now()));
+                    let session_keystroke_instance = session_playback_instance.clone();
+                    let sent = cb_sink.send(Box::new(move |siv: &mut Cursive| {
+                        controller::on_replayed_keystroke(
+                            siv,
+                            &mut session_keystroke_instance.write().unwrap(),
+                            keystroke,
+                            start + offset,
+                        );
+                    }));
+                    if sent.is_err() {
+                        return;
+                    }
+                }
+                let mut session = session_playback_instance.write().unwrap();
+                let _ = session.end(start + script.duration());
+            });
         })
         .build();
 
-    {
-        let mut siv = Cursive::default();
-
-        let cb_sink = siv.cb_sink().clone();
-        thread::spawn(move || loop {
-            let performance_iteration_instance = performance_background_instance.clone();
-            cb_sink
-                .send(Box::new(move |siv: &mut Cursive| {
-                    view::update_performance_display(
-                        siv,
-                        &performance_iteration_instance.read().unwrap(),
-                    );
-                }))
-                .unwrap();
-            thread::sleep(std::time::Duration::from_millis(
-                consts::PERFORMANCE_REFRESH_MS,
-            ));
-        });
+    run_cursive(
+        Cursive::default(),
+        view,
+        session_arc.clone(),
+        telemetry,
+        &config,
+    )?;
+
+    let session = session_arc.read().unwrap();
+    println!("{}", session.performance());
+    Ok(())
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let matches = get_matches();
+    if let Some(config_matches) = matches.subcommand_matches("config") {
+        return run_config(config_matches);
+    }
+    if let Some(export_matches) = matches.subcommand_matches("export") {
+        return run_export(export_matches);
+    }
+    if let Some(import_matches) = matches.subcommand_matches("import") {
+        return run_import(import_matches.value_of("FILE").unwrap());
+    }
+    if let Some(stats_matches) = matches.subcommand_matches("stats") {
+        return run_stats(stats_matches);
+    }
+    if let Some(script_matches) = matches.subcommand_matches("script") {
+        return run_script(script_matches, script_matches.value_of("FILE").unwrap());
+    }
+    if let Some(host_matches) = matches.subcommand_matches("host") {
+        return run_host(host_matches);
+    }
+    if let Some(join_matches) = matches.subcommand_matches("join") {
+        return run_join(join_matches);
+    }
+    if let Some(replay_matches) = matches.subcommand_matches("replay") {
+        return run_replay(replay_matches, replay_matches.value_of("FILE").unwrap());
+    }
 
-        siv.add_layer(view);
-        siv.run();
+    let config = get_config(&matches)?;
+    let telemetry = get_telemetry(&matches)?;
+    let ghost = match matches.value_of("ghost") {
+        Some(path) => Some(read_script(path)?),
+        None => None,
+    };
+    let seed = match ghost.as_ref().and_then(Script::seed) {
+        Some(seed) if !matches.is_present("seed") => seed,
+        _ => get_seed(&matches)?,
+    };
+    let mut source = get_source(&matches, &config, seed)?;
+    if let Some(ghost) = &ghost {
+        source = get_ghost_source(ghost, source);
     }
+    let record = matches.value_of("record");
+    let recorder = Recorder::new(source);
+    let recorded_words = recorder.words();
+    let model: Model = get_model_builder(&config)
+        .with_source(Box::new(recorder))
+        .build();
+    let mut session =
+        get_session(&matches, &config, model)?.with_personal_bests(get_personal_bests()?);
+    if record.is_some() {
+        session = session.with_recording();
+    }
+    if let Some(ghost) = &ghost {
+        session = session.with_ghost(Ghost::from_script(ghost));
+    }
+    if config.auto_pause_secs > 0 {
+        session = session.with_auto_pause(Duration::from_secs(config.auto_pause_secs));
+    }
+    if let Some(pace) = matches.value_of("pace") {
+        let (min_wpm, max_wpm) = parse_pace(pace)?;
+        let pacer = Pacer::new(min_wpm, StdRng::seed_from_u64(seed)).with_max_wpm(max_wpm);
+        session = session.with_pacer(pacer);
+    }
+    let has_rivals = session.has_rivals();
+    let session_arc = Arc::new(RwLock::new(session));
+    let session_on_start_instance = session_arc.clone();
 
-    let performance_print_summary_instance = performance_arc.clone();
-    performance_print_summary_instance
-        .write()
-        .unwrap()
-        .end()
-        .expect("The performance monitor should not have been ended yet.");
-    let performance = performance_print_summary_instance.read().unwrap();
-    println!("{}", performance);
+    let view = ViewBuilder::new()
+        .with_panel_size(config.panel_cols, config.panel_rows)
+        .with_initial_words(&session_arc.read().unwrap().model().get_words())
+        .with_edit_callback(get_edit_callback(session_arc.clone()))
+        .with_start_callback(move |siv: &mut Cursive| {
+            controller::on_start(siv, &mut session_on_start_instance.write().unwrap());
+        });
+    let view = if has_rivals {
+        view.with_race_panel().build()
+    } else {
+        view.build()
+    };
+
+    let mut siv = Cursive::default();
+    let session_on_pause_instance = session_arc.clone();
+    siv.add_global_callback(Key::Esc, move |siv: &mut Cursive| {
+        controller::on_pause(siv, &mut session_on_pause_instance.write().unwrap());
+    });
+    run_cursive(siv, view, session_arc.clone(), telemetry, &config)?;
+
+    let session = session_arc.read().unwrap();
+    print_results(&session, &config.layout()?);
+    println!("Seed: {}", seed);
+    save_history(&matches, &session, seed)?;
+
+    if let (Some(path), Some(recording)) = (record, session.recording()) {
+        let words = recorded_words.lock().unwrap().clone();
+        let recording = recording.clone().with_seed(seed).with_words(words);
+        fs::write(path, recording.to_string())?;
+    }
 
     Ok(())
 }
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
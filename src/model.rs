@@ -1,49 +1,98 @@
 use std::collections::VecDeque;
+use std::fmt;
 use std::iter;
 
+use unicode_width::UnicodeWidthStr;
+
 use crate::consts;
 use crate::iter::PeekingFoldWhileTrait;
+use crate::source::{IterSource, WordSource};
 
 type Row = Vec<String>;
 
-#[derive(Debug)]
-pub struct Model<I: Iterator<Item = String>> {
+pub struct Model {
     words: VecDeque<Row>,
     history: Vec<String>,
-    sampler: std::iter::Peekable<I>,
+    offset: usize,
+    source: Box<dyn WordSource>,
+    lookahead: Option<String>,
+    cols: usize,
+    rows: usize,
 }
 
-impl<I> Model<I>
-where
-    I: Iterator<Item = String>,
-{
-    pub fn new(it: I) -> Model<I> {
-        let mut sampler = it.peekable();
-        Model {
-            words: iter::repeat_with(|| Model::make_row(&mut sampler))
-                .take(consts::PANEL_ROWS)
-                .collect::<VecDeque<Row>>(),
+impl Model {
+    pub fn new(source: Box<dyn WordSource>) -> Model {
+        Model::with_panel_size(source, consts::PANEL_COLS, consts::PANEL_ROWS)
+    }
+
+    /// Lays the words out in `rows` rows at most `cols` columns wide.
+    fn with_panel_size(source: Box<dyn WordSource>, cols: usize, rows: usize) -> Model {
+        let mut model = Model {
+            words: VecDeque::new(),
             history: Vec::new(),
-            sampler,
-        }
+            offset: 0,
+            source,
+            lookahead: None,
+            cols,
+            rows,
+        };
+        model.fill();
+        model
     }
 
     pub fn get_history(&self) -> Vec<String> {
         self.history.clone()
     }
 
+    /// The number of words registered before the first displayed row.
+    pub fn get_offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The number of words registered so far.
+    pub fn get_progress(&self) -> usize {
+        self.offset + self.history.len()
+    }
+
+    pub fn source(&self) -> &dyn WordSource {
+        self.source.as_ref()
+    }
+
+    /// Replaces the word source. The row being typed is kept and the rows
+    /// after it are redrawn from the new source.
+    pub fn set_source(&mut self, source: Box<dyn WordSource>) {
+        self.source = source;
+        self.lookahead = None;
+        self.words.truncate(1);
+        self.fill();
+    }
+
     pub fn register(&mut self, entry: &str) {
+        if let Some(expected) = self.get_current_word() {
+            self.source.feedback(&expected, entry);
+        }
         self.history.push(entry.to_owned());
-        if self.history.len() == self.first_row().len() {
+        if self.history.len() == self.first_row().map_or(0, Vec::len) {
             self.advance();
             self.history.clear();
         }
     }
 
     fn advance(&mut self) {
-        let row = self.get_row();
-        self.words.push_back(row);
-        self.words.pop_front();
+        if let Some(row) = self.words.pop_front() {
+            self.offset += row.len();
+        }
+        self.fill();
+    }
+
+    fn fill(&mut self) {
+        while self.words.len() < self.rows {
+            let row = self.make_row();
+            if row.is_empty() {
+                break;
+            }
+            self.words.push_back(row);
+        }
     }
 
     pub fn get_words(&self) -> Vec<String> {
@@ -55,52 +104,90 @@ where
         self.get_words().get(i).map(|word| word.to_owned())
     }
 
-    fn first_row(&self) -> &Row {
-        self.words.front().unwrap()
+    fn first_row(&self) -> Option<&Row> {
+        self.words.front()
     }
 
-    fn get_row(&mut self) -> Row {
-        Model::make_row(&mut self.sampler)
+    fn make_row(&mut self) -> Row {
+        let source = &mut self.source;
+        let cols = self.cols;
+        let mut it = self
+            .lookahead
+            .take()
+            .into_iter()
+            .chain(iter::from_fn(|| source.next_word()))
+            .peekable();
+        let row = it
+            .peeking_fold_while(Vec::new(), |mut acc, (curr, peek)| {
+                acc.push(curr.clone());
+                let next = match peek {
+                    Some(next) => next,
+                    None => return Ok(acc),
+                };
+                let current_width = acc.iter().map(|word| word.width()).sum::<usize>() + acc.len();
+                if current_width + next.width() > cols {
+                    Err(acc)
+                } else {
+                    Ok(acc)
+                }
+            })
+            .unwrap_or_else(|acc| acc);
+        self.lookahead = it.next();
+        row
     }
+}
 
-    fn make_row(it: &mut std::iter::Peekable<I>) -> Row {
-        it.peeking_fold_while(Vec::new(), |mut acc, (curr, peek)| {
-            acc.push(curr.clone());
-            let next = peek.expect("The provided sampler should not end.");
-            let current_width = acc.iter().map(String::len).sum::<usize>() + acc.len();
-            if current_width + next.len() > consts::PANEL_COLS {
-                Err(acc)
-            } else {
-                Ok(acc)
-            }
-        })
-        .unwrap_or_else(|acc| acc)
+impl fmt::Debug for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Model")
+            .field("words", &self.words)
+            .field("history", &self.history)
+            .field("source", &self.source.name())
+            .finish()
     }
 }
 
-#[derive(Default)]
-pub struct ModelBuilder<I>
-where
-    I: Iterator<Item = String>,
-{
-    word_stream: Option<I>,
+pub struct ModelBuilder {
+    source: Option<Box<dyn WordSource>>,
+    cols: usize,
+    rows: usize,
 }
 
-impl<I> ModelBuilder<I>
-where
-    I: Iterator<Item = String>,
-{
-    pub fn new() -> ModelBuilder<I> {
-        ModelBuilder { word_stream: None }
+impl Default for ModelBuilder {
+    fn default() -> ModelBuilder {
+        ModelBuilder::new()
+    }
+}
+
+impl ModelBuilder {
+    pub fn new() -> ModelBuilder {
+        ModelBuilder {
+            source: None,
+            cols: consts::PANEL_COLS,
+            rows: consts::PANEL_ROWS,
+        }
     }
 
-    pub fn with_word_stream(mut self, word_stream: I) -> ModelBuilder<I> {
-        self.word_stream = Some(word_stream);
+    pub fn with_panel_size(mut self, cols: usize, rows: usize) -> ModelBuilder {
+        self.cols = cols;
+        self.rows = rows;
         self
     }
 
-    pub fn build(self) -> Model<I> {
-        Model::new(self.word_stream.unwrap())
+    pub fn with_word_stream<I>(self, word_stream: I) -> ModelBuilder
+    where
+        I: Iterator<Item = String> + Send + Sync + 'static,
+    {
+        self.with_source(Box::new(IterSource::new(word_stream, "stream")))
+    }
+
+    pub fn with_source(mut self, source: Box<dyn WordSource>) -> ModelBuilder {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn build(self) -> Model {
+        Model::with_panel_size(self.source.unwrap(), self.cols, self.rows)
     }
 }
 
@@ -110,13 +197,13 @@ mod tests {
 
     #[test]
     fn test_initial_values() {
-        let lexicon = vec![
+        let lexicon = [
             "sphinx".to_string(),
             "of".to_string(),
             "black".to_string(),
             "quartz".to_string(),
         ];
-        let stream = lexicon.iter().cloned().cycle();
+        let stream = IntoIterator::into_iter(lexicon).cycle();
         let model = ModelBuilder::new().with_word_stream(stream).build();
         assert_eq!(model.get_history().len(), 0);
         assert_eq!(model.get_current_word(), Some("sphinx".to_string()));
@@ -124,17 +211,107 @@ mod tests {
 
     #[test]
     fn test_register() {
-        let lexicon = vec![
+        let lexicon = [
             "sphinx".to_string(),
             "of".to_string(),
             "black".to_string(),
             "quartz".to_string(),
         ];
-        let stream = lexicon.iter().cloned().cycle();
+        let stream = IntoIterator::into_iter(lexicon).cycle();
         let mut model = ModelBuilder::new().with_word_stream(stream).build();
         model.register("sphx");
         assert_eq!(model.get_history().len(), 1);
         assert_eq!(model.get_history().first(), Some(&"sphx".to_string()));
         assert_eq!(model.get_current_word(), Some("of".to_string()));
+        assert_eq!(model.get_progress(), 1);
+    }
+
+    #[test]
+    fn test_offset() {
+        let stream = iter::repeat("sphinx".to_string());
+        let mut model = ModelBuilder::new().with_word_stream(stream).build();
+        let row_len = model.first_row().unwrap().len();
+        for _ in 0..row_len + 1 {
+            model.register("sphinx");
+        }
+        assert_eq!(model.get_offset(), row_len);
+        assert_eq!(model.get_progress(), row_len + 1);
+    }
+
+    #[test]
+    fn test_row_display_width() {
+        let stream = iter::repeat("日本語".to_string());
+        let model = ModelBuilder::new().with_word_stream(stream).build();
+        let row = model.first_row().unwrap();
+        assert_eq!(row.len(), (consts::PANEL_COLS + 1) / 7);
+    }
+
+    #[test]
+    fn test_panel_size() {
+        let stream = iter::repeat("sphinx".to_string());
+        let model = ModelBuilder::new()
+            .with_panel_size(20, 3)
+            .with_word_stream(stream)
+            .build();
+        assert_eq!(model.words.len(), 3);
+        assert_eq!(model.first_row().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_finite_source() {
+        let lexicon = vec!["sphinx", "of", "black"];
+        let stream = lexicon.clone().into_iter().map(String::from);
+        let source = IterSource::new(stream, "finite").with_total_words(3);
+        let mut model = ModelBuilder::new().with_source(Box::new(source)).build();
+        assert_eq!(model.source().total_words(), Some(3));
+        assert_eq!(model.get_words(), lexicon);
+        for word in lexicon.iter() {
+            model.register(word);
+        }
+        assert_eq!(model.get_current_word(), None);
+        assert!(model.get_words().is_empty());
+    }
+
+    #[test]
+    fn test_set_source() {
+        let stream = iter::repeat("sphinx".to_string());
+        let mut model = ModelBuilder::new().with_word_stream(stream).build();
+        model.register("sphinx");
+        let source = IterSource::new(iter::repeat("quartz".to_string()), "quartz");
+        model.set_source(Box::new(source));
+        assert_eq!(model.source().name(), "quartz");
+        assert_eq!(model.get_current_word(), Some("sphinx".to_string()));
+        assert_eq!(model.get_words().last(), Some(&"quartz".to_string()));
+    }
+
+    #[test]
+    fn test_feedback() {
+        use std::sync::{Arc, Mutex};
+
+        struct Recorder(Arc<Mutex<Vec<(String, String)>>>);
+
+        impl WordSource for Recorder {
+            fn next_word(&mut self) -> Option<String> {
+                Some("vow".to_string())
+            }
+
+            fn name(&self) -> &str {
+                "recorder"
+            }
+
+            fn feedback(&mut self, expected: &str, entered: &str) {
+                let mut log = self.0.lock().unwrap();
+                log.push((expected.to_owned(), entered.to_owned()));
+            }
+        }
+
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let source = Recorder(log.clone());
+        let mut model = ModelBuilder::new().with_source(Box::new(source)).build();
+        model.register("vw");
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![("vow".to_string(), "vw".to_string())]
+        );
     }
 }
@@ -0,0 +1,611 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::iter;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::race::{PlayerProgress, Scoreboard};
+
+/// How long the host waits for a player to accept a message before
+/// disconnecting it.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The longest line read as a message, leaving room for a long list of
+/// stop words in the welcome.
+const MAX_MESSAGE_LEN: u64 = 1 << 20;
+
+/// Everything besides the seed that decides which words are drawn. Players
+/// must draw words the same way as the host to race on the same words.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WordSettings {
+    /// The name of the word source, e.g. the file stem of the lexicon.
+    pub source: String,
+    pub sample_size: usize,
+    pub punctuation: bool,
+    pub numbers: bool,
+    /// The order of the Markov chain when generating from a corpus.
+    pub order: Option<usize>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    pub alphabet: Option<String>,
+    pub include: Option<String>,
+    pub exclude: Option<String>,
+    /// The stop words themselves, sorted, as their file may be anywhere.
+    pub stop_words: Vec<String>,
+}
+
+impl WordSettings {
+    /// The names of the settings that differ from `other`.
+    pub fn differences(&self, other: &WordSettings) -> Vec<&'static str> {
+        [
+            ("source", self.source == other.source),
+            ("sample_size", self.sample_size == other.sample_size),
+            ("punctuation", self.punctuation == other.punctuation),
+            ("numbers", self.numbers == other.numbers),
+            ("order", self.order == other.order),
+            ("min_length", self.min_length == other.min_length),
+            ("max_length", self.max_length == other.max_length),
+            ("alphabet", self.alphabet == other.alphabet),
+            ("include", self.include == other.include),
+            ("exclude", self.exclude == other.exclude),
+            ("stop_words", self.stop_words == other.stop_words),
+        ]
+        .iter()
+        .filter(|(_, same)| !same)
+        .map(|(name, _)| *name)
+        .collect()
+    }
+}
+
+/// A message of the multiplayer protocol. Each message is sent as a single
+/// line of JSON tagged with its type:
+///
+/// ```text
+/// {"type":"join","name":"alice"}
+/// {"type":"welcome","seed":42,"settings":{"source":"top1000",...}}
+/// {"type":"start"}
+/// {"type":"progress","name":"alice","words":12,"wpm":48.0}
+/// {"type":"finish","name":"alice","words":30,"wpm":52.5}
+/// {"type":"leave","name":"alice"}
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    /// Sent by a player when connecting to the host.
+    Join { name: String },
+    /// The host's answer to `Join`, with what every player needs to draw the
+    /// same words.
+    Welcome { seed: u64, settings: WordSettings },
+    /// The host's answer to a `Join` it cannot accept.
+    Reject { reason: String },
+    /// Sent by the host when the race begins.
+    Start,
+    Progress {
+        name: String,
+        words: usize,
+        wpm: f32,
+    },
+    Finish {
+        name: String,
+        words: usize,
+        wpm: f32,
+    },
+    /// Sent by the host when a player hangs up.
+    Leave { name: String },
+}
+
+impl Message {
+    /// The scoreboard entry carried by a `Progress` or `Finish` message.
+    fn player_progress(&self) -> Option<(&str, PlayerProgress)> {
+        match self {
+            Message::Progress { name, words, wpm } | Message::Finish { name, words, wpm } => {
+                let progress = PlayerProgress {
+                    words: *words,
+                    wpm: *wpm,
+                    finished: matches!(self, Message::Finish { .. }),
+                };
+                Some((name, progress))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Reads the next message, or `None` once the other end hangs up. Lines
+/// longer than `MAX_MESSAGE_LEN` are refused rather than buffered.
+pub fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<Message>, NetError> {
+    let mut line = String::new();
+    let len = reader.by_ref().take(MAX_MESSAGE_LEN).read_line(&mut line)?;
+    if len == 0 {
+        return Ok(None);
+    }
+    if len as u64 == MAX_MESSAGE_LEN && !line.ends_with('\n') {
+        return Err(NetError::Protocol("The message is too long.".to_string()));
+    }
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+pub fn write_message<W: Write>(writer: &mut W, message: &Message) -> Result<(), NetError> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+type SharedStream = Arc<Mutex<TcpStream>>;
+
+#[derive(Debug, Default)]
+struct Lobby {
+    clients: Vec<(String, SharedStream)>,
+    started: bool,
+}
+
+/// Sends `message` to every client except `sender`. The lobby is only locked
+/// to list the clients, so that a slow client holds up no one else. Clients
+/// that cannot be written to are disconnected, which makes them leave.
+fn broadcast(lobby: &Mutex<Lobby>, message: &Message, sender: Option<&str>) {
+    let recipients: Vec<SharedStream> = lobby
+        .lock()
+        .unwrap()
+        .clients
+        .iter()
+        .filter(|(name, _)| Some(name.as_str()) != sender)
+        .map(|(_, stream)| stream.clone())
+        .collect();
+    for stream in recipients {
+        let mut stream = stream.lock().unwrap();
+        if write_message(&mut *stream, message).is_err() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+/// Accepts players and relays their progress to each other. The host takes
+/// part in the race too; its own progress is only broadcast.
+#[derive(Debug)]
+pub struct Host {
+    name: String,
+    seed: u64,
+    settings: WordSettings,
+    listener: TcpListener,
+    lobby: Arc<Mutex<Lobby>>,
+    scoreboard: Arc<Mutex<Scoreboard>>,
+}
+
+impl Host {
+    pub fn bind<A: ToSocketAddrs>(
+        address: A,
+        name: &str,
+        seed: u64,
+        settings: WordSettings,
+    ) -> Result<Host, NetError> {
+        Ok(Host {
+            name: name.to_owned(),
+            seed,
+            settings,
+            listener: TcpListener::bind(address)?,
+            lobby: Arc::new(Mutex::new(Lobby::default())),
+            scoreboard: Arc::new(Mutex::new(Scoreboard::new())),
+        })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, NetError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    pub fn scoreboard(&self) -> Arc<Mutex<Scoreboard>> {
+        self.scoreboard.clone()
+    }
+
+    /// Accepts players in the background until the process exits.
+    pub fn serve(&self) -> Result<(), NetError> {
+        let listener = self.listener.try_clone()?;
+        let handler = self.handler();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handler = handler.clone();
+                thread::spawn(move || {
+                    let _ = handler.handle(stream);
+                });
+            }
+        });
+        Ok(())
+    }
+
+    fn handler(&self) -> ClientHandler {
+        ClientHandler {
+            host_name: self.name.clone(),
+            welcome: Message::Welcome {
+                seed: self.seed,
+                settings: self.settings.clone(),
+            },
+            lobby: self.lobby.clone(),
+            scoreboard: self.scoreboard.clone(),
+        }
+    }
+
+    /// Starts the race for every player who has joined.
+    pub fn start(&self) {
+        self.lobby.lock().unwrap().started = true;
+        broadcast(&self.lobby, &Message::Start, None);
+    }
+
+    /// Shares the host's own progress with every player.
+    pub fn report(&self, message: &Message) {
+        broadcast(&self.lobby, message, None);
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ClientHandler {
+    host_name: String,
+    welcome: Message,
+    lobby: Arc<Mutex<Lobby>>,
+    scoreboard: Arc<Mutex<Scoreboard>>,
+}
+
+impl ClientHandler {
+    fn handle(&self, stream: TcpStream) -> Result<(), NetError> {
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let name = match read_message(&mut reader)? {
+            Some(Message::Join { name }) => name,
+            _ => return Err(NetError::Protocol("Expected a join message.".to_string())),
+        };
+        let players = match self.reserve(&name) {
+            Ok(players) => players,
+            Err(reason) => {
+                let reason = reason.to_string();
+                return write_message(&mut &stream, &Message::Reject { reason });
+            }
+        };
+        let result = self.race(&name, stream, players, &mut reader);
+        self.leave(&name);
+        result
+    }
+
+    /// Claims `name` for a new player unless the race has started or the
+    /// name is taken, returning the players already there.
+    fn reserve(&self, name: &str) -> Result<Vec<(String, PlayerProgress)>, &'static str> {
+        let lobby = self.lobby.lock().unwrap();
+        let mut scoreboard = self.scoreboard.lock().unwrap();
+        if lobby.started {
+            Err("The race has already started.")
+        } else if name == self.host_name || scoreboard.contains(name) {
+            Err("That name is taken.")
+        } else {
+            let players = scoreboard.players();
+            scoreboard.update(name, PlayerProgress::default());
+            Ok(players)
+        }
+    }
+
+    /// Welcomes the player, then relays its progress until it hangs up.
+    fn race(
+        &self,
+        name: &str,
+        mut stream: TcpStream,
+        players: Vec<(String, PlayerProgress)>,
+        reader: &mut BufReader<TcpStream>,
+    ) -> Result<(), NetError> {
+        write_message(&mut stream, &self.welcome)?;
+        let host = (self.host_name.clone(), PlayerProgress::default());
+        for (other, progress) in iter::once(host).chain(players) {
+            let message = Message::Progress {
+                name: other,
+                words: progress.words,
+                wpm: progress.wpm,
+            };
+            write_message(&mut stream, &message)?;
+        }
+        let stream = Arc::new(Mutex::new(stream));
+        let started = {
+            let mut lobby = self.lobby.lock().unwrap();
+            lobby.clients.push((name.to_owned(), stream.clone()));
+            lobby.started
+        };
+        // The host may have started since the name was reserved, before the
+        // player could hear about it. A second start is ignored.
+        if started {
+            write_message(&mut *stream.lock().unwrap(), &Message::Start)?;
+        }
+        let joined = Message::Progress {
+            name: name.to_owned(),
+            words: 0,
+            wpm: 0.0,
+        };
+        broadcast(&self.lobby, &joined, Some(name));
+        while let Some(message) = read_message(reader)? {
+            if let Some((sender, progress)) = message.player_progress() {
+                if sender != name {
+                    continue;
+                }
+                self.scoreboard.lock().unwrap().update(name, progress);
+                broadcast(&self.lobby, &message, Some(name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets a player who has hung up or could not be written to.
+    fn leave(&self, name: &str) {
+        self.lobby
+            .lock()
+            .unwrap()
+            .clients
+            .retain(|(other, _)| other != name);
+        self.scoreboard.lock().unwrap().remove(name);
+        let name = name.to_owned();
+        broadcast(&self.lobby, &Message::Leave { name }, None);
+    }
+}
+
+/// A player taking part in a race hosted elsewhere.
+#[derive(Debug)]
+pub struct Client {
+    seed: u64,
+    settings: WordSettings,
+    stream: Mutex<TcpStream>,
+    reader: Option<BufReader<TcpStream>>,
+}
+
+impl Client {
+    /// Joins the host at `address` as `name` and waits to be welcomed.
+    pub fn connect<A: ToSocketAddrs>(address: A, name: &str) -> Result<Client, NetError> {
+        let mut stream = TcpStream::connect(address)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let name = name.to_owned();
+        write_message(&mut stream, &Message::Join { name })?;
+        match read_message(&mut reader)? {
+            Some(Message::Welcome { seed, settings }) => Ok(Client {
+                seed,
+                settings,
+                stream: Mutex::new(stream),
+                reader: Some(reader),
+            }),
+            Some(Message::Reject { reason }) => Err(NetError::Rejected(reason)),
+            _ => Err(NetError::Protocol(
+                "Expected a welcome message.".to_string(),
+            )),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// How the host draws its words.
+    pub fn settings(&self) -> &WordSettings {
+        &self.settings
+    }
+
+    /// Keeps `scoreboard` up to date in the background and calls `on_start`
+    /// when the host starts the race.
+    pub fn listen<F>(&mut self, scoreboard: Arc<Mutex<Scoreboard>>, on_start: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut reader = self
+            .reader
+            .take()
+            .expect("The client is already listening.");
+        thread::spawn(move || {
+            let mut on_start = Some(on_start);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if let Message::Start = message {
+                    if let Some(on_start) = on_start.take() {
+                        on_start();
+                    }
+                } else if let Message::Leave { name } = &message {
+                    scoreboard.lock().unwrap().remove(name);
+                } else if let Some((name, progress)) = message.player_progress() {
+                    scoreboard.lock().unwrap().update(name, progress);
+                }
+            }
+        });
+    }
+
+    pub fn report(&self, message: &Message) -> Result<(), NetError> {
+        write_message(&mut *self.stream.lock().unwrap(), message)
+    }
+}
+
+/// Hangs up, so that the host drops the player from the race.
+impl Drop for Client {
+    fn drop(&mut self) {
+        let _ = self.stream.lock().unwrap().shutdown(Shutdown::Both);
+    }
+}
+
+#[derive(Debug)]
+pub enum NetError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Protocol(String),
+    Rejected(String),
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetError::Io(err) => write!(f, "Connection failed: {}", err),
+            NetError::Json(err) => write!(f, "Invalid message: {}", err),
+            NetError::Protocol(message) => write!(f, "Unexpected message: {}", message),
+            NetError::Rejected(reason) => write!(f, "The host turned us away: {}", reason),
+        }
+    }
+}
+
+impl error::Error for NetError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            NetError::Io(err) => Some(err),
+            NetError::Json(err) => Some(err),
+            NetError::Protocol(_) | NetError::Rejected(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for NetError {
+    fn from(err: io::Error) -> NetError {
+        NetError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for NetError {
+    fn from(err: serde_json::Error) -> NetError {
+        NetError::Json(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    fn wait_for<F: Fn() -> bool>(condition: F) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert!(Instant::now() < deadline, "Timed out.");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn progress(name: &str, words: usize) -> Message {
+        Message::Progress {
+            name: name.to_string(),
+            words,
+            wpm: 30.0,
+        }
+    }
+
+    #[test]
+    fn test_message_format() {
+        let mut buffer = Vec::new();
+        write_message(&mut buffer, &progress("alice", 3)).unwrap();
+        write_message(&mut buffer, &Message::Start).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer.clone()).unwrap(),
+            "{\"type\":\"progress\",\"name\":\"alice\",\"words\":3,\"wpm\":30.0}\n\
+             {\"type\":\"start\"}\n"
+        );
+        let mut reader = buffer.as_slice();
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some(progress("alice", 3))
+        );
+        assert_eq!(read_message(&mut reader).unwrap(), Some(Message::Start));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_race() {
+        let settings = WordSettings {
+            source: "top1000".to_string(),
+            ..WordSettings::default()
+        };
+        let host = Host::bind("127.0.0.1:0", "host", 42, settings.clone()).unwrap();
+        host.serve().unwrap();
+        let address = host.local_addr().unwrap();
+
+        let mut alice = Client::connect(address, "alice").unwrap();
+        assert_eq!(alice.seed(), 42);
+        assert_eq!(alice.settings(), &settings);
+        let alice_scoreboard = Arc::new(Mutex::new(Scoreboard::new()));
+        let (started, on_start) = mpsc::channel();
+        alice.listen(alice_scoreboard.clone(), move || started.send(()).unwrap());
+
+        let mut bob = Client::connect(address, "bob").unwrap();
+        let bob_scoreboard = Arc::new(Mutex::new(Scoreboard::new()));
+        bob.listen(bob_scoreboard.clone(), || ());
+        match Client::connect(address, "bob") {
+            Err(NetError::Rejected(_)) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        wait_for(|| alice_scoreboard.lock().unwrap().contains("bob"));
+        wait_for(|| bob_scoreboard.lock().unwrap().contains("alice"));
+        wait_for(|| bob_scoreboard.lock().unwrap().contains("host"));
+
+        host.start();
+        on_start.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        alice.report(&progress("alice", 3)).unwrap();
+        host.report(&progress("host", 2));
+        let alice_words = || bob_scoreboard.lock().unwrap().players()[0].1.words;
+        wait_for(|| alice_words() == 3);
+        wait_for(|| host.scoreboard().lock().unwrap().players()[0].1.words == 3);
+        let host_words = || alice_scoreboard.lock().unwrap().players()[1].1.words;
+        wait_for(|| host_words() == 2);
+
+        match Client::connect(address, "carol") {
+            Err(NetError::Rejected(_)) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        drop(bob);
+        wait_for(|| !host.scoreboard().lock().unwrap().contains("bob"));
+        wait_for(|| !alice_scoreboard.lock().unwrap().contains("bob"));
+    }
+
+    #[test]
+    fn test_join_while_starting() {
+        let host = Host::bind("127.0.0.1:0", "host", 42, WordSettings::default()).unwrap();
+        let address = host.local_addr().unwrap();
+        let player = TcpStream::connect(address).unwrap();
+        let (stream, _) = host.listener.accept().unwrap();
+        let handler = host.handler();
+        // The host starts between the name being reserved and the player
+        // being added to the lobby.
+        let players = handler.reserve("alice").unwrap();
+        host.start();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            handler.race("alice", stream, players, &mut reader)
+        });
+
+        player
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut reader = BufReader::new(player);
+        let mut messages = iter::from_fn(|| read_message(&mut reader).unwrap());
+        assert!(matches!(messages.next(), Some(Message::Welcome { .. })));
+        assert!(matches!(messages.next(), Some(Message::Progress { .. })));
+        assert_eq!(messages.next(), Some(Message::Start));
+    }
+
+    #[test]
+    fn test_long_message() {
+        let mut line = vec![b'x'; MAX_MESSAGE_LEN as usize + 1];
+        line.push(b'\n');
+        match read_message(&mut &line[..]) {
+            Err(NetError::Protocol(_)) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_word_settings() {
+        let settings = WordSettings {
+            source: "top1000".to_string(),
+            punctuation: true,
+            ..WordSettings::default()
+        };
+        assert!(settings.differences(&settings).is_empty());
+        let other = WordSettings {
+            stop_words: vec!["the".to_string()],
+            ..WordSettings::default()
+        };
+        assert_eq!(
+            settings.differences(&other),
+            vec!["source", "punctuation", "stop_words"]
+        );
+    }
+}
@@ -1,6 +1,13 @@
+use std::collections::{BTreeMap, HashMap};
 use std::error;
 use std::fmt;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::compare::Comparison;
+use crate::consts;
+use crate::keyboard::{Finger, Hand, KeyStat, KeyStats, Layout};
 
 #[derive(Debug, Default)]
 pub struct PerformanceMonitor {
@@ -8,6 +15,31 @@ pub struct PerformanceMonitor {
     end: Option<Instant>,
     correct: u32,
     attempted: u32,
+    comparison: Comparison,
+    /// Finished pauses, from when to when.
+    pauses: Vec<(Instant, Instant)>,
+    paused_at: Option<Instant>,
+    /// How many times as fast as the wall clock the session runs, when
+    /// replaying.
+    speed: Option<f64>,
+    /// When each word was submitted, as time elapsed outside pauses.
+    words: Vec<(Duration, bool)>,
+    /// The length of each correct word and how long it took to type.
+    word_times: Vec<(usize, Duration)>,
+    keys: KeyStats,
+    bigrams: HashMap<(char, char), u32>,
+}
+
+/// The performance within the second of a session ending at `elapsed`, for
+/// graphs. Raw WPM counts every attempted word, net WPM only the correct
+/// ones.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub elapsed: Duration,
+    pub raw_wpm: f32,
+    pub net_wpm: f32,
+    /// The mistakes made since the previous sample.
+    pub errors: u32,
 }
 
 impl PerformanceMonitor {
@@ -17,29 +49,128 @@ impl PerformanceMonitor {
             end: None,
             correct: 0,
             attempted: 0,
+            comparison: Comparison::new(),
+            pauses: Vec::new(),
+            paused_at: None,
+            speed: None,
+            words: Vec::new(),
+            word_times: Vec::new(),
+            keys: KeyStats::new(),
+            bigrams: HashMap::new(),
+        }
+    }
+
+    pub fn with_comparison(mut self, comparison: Comparison) -> PerformanceMonitor {
+        self.comparison = comparison;
+        self
+    }
+
+    /// Runs the clock `speed` times as fast as the wall clock from the
+    /// start, so that a replay is timed as it was recorded.
+    pub fn with_speed(mut self, speed: f64) -> PerformanceMonitor {
+        self.speed = Some(speed);
+        self
+    }
+
+    /// The time in the session at `Instant::now()`.
+    pub fn now(&self) -> Instant {
+        let now = Instant::now();
+        match (self.start, self.speed) {
+            (Some(start), Some(speed)) => {
+                start + now.saturating_duration_since(start).mul_f64(speed)
+            }
+            _ => now,
         }
     }
 
     pub fn start(&mut self) -> Result<(), PerformanceMonitorError> {
+        self.start_at(self.now())
+    }
+
+    pub fn start_at(&mut self, now: Instant) -> Result<(), PerformanceMonitorError> {
         match self.start {
             Some(_) => Err(PerformanceMonitorError),
             None => {
-                self.start = Some(Instant::now());
+                self.start = Some(now);
                 Ok(())
             }
         }
     }
 
     pub fn end(&mut self) -> Result<(), PerformanceMonitorError> {
+        self.end_at(self.now())
+    }
+
+    /// Ends the session, along with any pause under way.
+    pub fn end_at(&mut self, now: Instant) -> Result<(), PerformanceMonitorError> {
         match self.end {
             Some(_) => Err(PerformanceMonitorError),
             None => {
-                self.end = Some(Instant::now());
+                if self.is_paused() {
+                    self.resume_at(now)?;
+                }
+                self.end = Some(now);
                 Ok(())
             }
         }
     }
 
+    /// Stops the clock until `resume_at`. Only a session under way can be
+    /// paused.
+    pub fn pause_at(&mut self, now: Instant) -> Result<(), PerformanceMonitorError> {
+        if self.start.is_none() || self.end.is_some() || self.paused_at.is_some() {
+            return Err(PerformanceMonitorError);
+        }
+        self.paused_at = Some(now);
+        Ok(())
+    }
+
+    /// Restarts the clock, returning how long it was paused.
+    pub fn resume_at(&mut self, now: Instant) -> Result<Duration, PerformanceMonitorError> {
+        let paused_at = self.paused_at.take().ok_or(PerformanceMonitorError)?;
+        let now = now.max(paused_at);
+        self.pauses.push((paused_at, now));
+        Ok(now - paused_at)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// The time from the start to `now`, frozen once ended or paused,
+    /// leaving out pauses.
+    pub fn elapsed_at(&self, now: Instant) -> Duration {
+        let start = match self.start {
+            Some(start) => start,
+            None => return Duration::default(),
+        };
+        let until = self.end.or(self.paused_at).unwrap_or(now);
+        let paused: Duration = self
+            .pauses
+            .iter()
+            .map(|(from, to)| (*to).min(until).saturating_duration_since(*from))
+            .sum();
+        until
+            .saturating_duration_since(start)
+            .saturating_sub(paused)
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.start.is_some()
+    }
+
+    pub fn started_at(&self) -> Option<Instant> {
+        self.start
+    }
+
+    pub fn ended_at(&self) -> Option<Instant> {
+        self.end
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.end.is_some()
+    }
+
     pub fn correct(&self) -> u32 {
         self.correct
     }
@@ -48,6 +179,10 @@ impl PerformanceMonitor {
         self.attempted
     }
 
+    pub fn comparison(&self) -> &Comparison {
+        &self.comparison
+    }
+
     pub fn accuracy(&self) -> Result<f32, PerformanceMonitorError> {
         match self.attempted {
             0 => Err(PerformanceMonitorError),
@@ -55,12 +190,10 @@ impl PerformanceMonitor {
         }
     }
 
+    /// The time typed so far, leaving out pauses.
     pub fn duration(&self) -> Result<std::time::Duration, PerformanceMonitorError> {
         match self.start {
-            Some(start) => match self.end {
-                Some(end) => Ok(end - start),
-                _ => Ok(Instant::now() - start),
-            },
+            Some(_) => Ok(self.elapsed_at(self.now())),
             None => Err(PerformanceMonitorError),
         }
     }
@@ -76,24 +209,287 @@ impl PerformanceMonitor {
         self.wps().map(|wps| wps * 60f32)
     }
 
+    /// Words per minute counting mistakes too.
+    pub fn raw_wpm(&self) -> Result<f32, PerformanceMonitorError> {
+        self.duration()
+            .map(|duration| self.attempted as f32 * 60f32 / duration.as_secs_f32())
+    }
+
+    pub fn summary(&self) -> Summary {
+        Summary {
+            wpm: self.wpm().unwrap_or_default(),
+            raw_wpm: self.raw_wpm().unwrap_or_default(),
+            accuracy: self.accuracy().unwrap_or_default(),
+            correct: self.correct,
+            attempted: self.attempted,
+            duration_ms: self.duration().unwrap_or_default().as_millis() as u64,
+        }
+    }
+
+    /// The correct words per minute over the last `window`, or over the
+    /// whole session if it is shorter.
+    pub fn rolling_wpm(&self, window: Duration) -> Result<f32, PerformanceMonitorError> {
+        let duration = self.duration()?;
+        let window = window.min(duration);
+        if window == Duration::from_secs(0) {
+            return Err(PerformanceMonitorError);
+        }
+        let from = duration - window;
+        let correct = self
+            .words
+            .iter()
+            .filter(|(at, correct)| *correct && *at > from)
+            .count();
+        Ok(correct as f32 * 60f32 / window.as_secs_f32())
+    }
+
+    /// The fastest correct word, timed from its first keystroke to its
+    /// submission, in words of five characters per minute.
+    pub fn burst_wpm(&self) -> Result<f32, PerformanceMonitorError> {
+        self.word_times
+            .iter()
+            .filter(|(_, duration)| *duration > Duration::default())
+            .map(|(chars, duration)| *chars as f32 / 5f32 * 60f32 / duration.as_secs_f32())
+            .fold(None, |burst: Option<f32>, wpm| {
+                Some(burst.map_or(wpm, |burst| burst.max(wpm)))
+            })
+            .ok_or(PerformanceMonitorError)
+    }
+
+    /// The correct words per minute within each full second so far, unlike
+    /// `samples` which averages from the start.
+    pub fn wpm_per_second(&self) -> Vec<f32> {
+        let duration = match self.duration() {
+            Ok(duration) => duration,
+            Err(_) => return Vec::new(),
+        };
+        let mut counts = vec![0u32; duration.as_secs() as usize];
+        for (at, _) in self.words.iter().filter(|(_, correct)| *correct) {
+            let second = at.as_nanos().saturating_sub(1) / 1_000_000_000;
+            if let Some(count) = counts.get_mut(second as usize) {
+                *count += 1;
+            }
+        }
+        counts.iter().map(|count| *count as f32 * 60f32).collect()
+    }
+
+    /// The coefficient of variation of the WPM within each second: 0 for a
+    /// perfectly steady pace, higher the more it varies.
+    pub fn consistency(&self) -> Result<f32, PerformanceMonitorError> {
+        let wpm = self.wpm_per_second();
+        if wpm.len() < 2 {
+            return Err(PerformanceMonitorError);
+        }
+        let mean = wpm.iter().sum::<f32>() / wpm.len() as f32;
+        if mean == 0f32 {
+            return Err(PerformanceMonitorError);
+        }
+        let variance = wpm.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / wpm.len() as f32;
+        Ok(variance.sqrt() / mean)
+    }
+
     pub fn register(&mut self, entered: &str, expected: &str) {
+        self.register_at(entered, expected, self.now())
+    }
+
+    pub fn register_at(&mut self, entered: &str, expected: &str, now: Instant) {
+        let correct = self.comparison.words_eq(entered, expected);
         self.attempted += 1;
-        if entered == expected {
+        if correct {
             self.correct += 1;
         }
+        self.words.push((self.elapsed_at(now), correct));
+        let chars: Vec<char> = expected.chars().collect();
+        for pair in chars.windows(2) {
+            *self.bigrams.entry((pair[0], pair[1])).or_default() += 1;
+        }
+    }
+
+    /// Times a correct word from its first keystroke to its submission.
+    pub fn register_word_time(&mut self, word: &str, duration: Duration) {
+        self.word_times.push((word.chars().count(), duration));
+    }
+
+    /// Counts a keystroke against the key that should have been pressed.
+    /// `latency` is the time since the previous keystroke, if any.
+    pub fn register_key(&mut self, expected: char, correct: bool, latency: Option<Duration>) {
+        self.keys.record(expected, correct, latency);
+    }
+
+    pub fn keys(&self) -> &KeyStats {
+        &self.keys
+    }
+
+    /// The key statistics grouped by the finger and hand of `layout` typing
+    /// each key, and how the words submitted move between fingers.
+    pub fn finger_stats(&self, layout: &Layout) -> FingerStats {
+        let mut stats = FingerStats::default();
+        for (key, stat) in self.keys.iter() {
+            if let Some(finger) = layout.finger(key) {
+                stats.fingers.entry(finger).or_default().add(stat);
+                stats.hands.entry(finger.hand()).or_default().add(stat);
+            }
+        }
+        for ((first, second), count) in self.bigrams.iter() {
+            if let (Some(x), Some(y)) = (layout.finger(*first), layout.finger(*second)) {
+                stats.bigrams += count;
+                if x == y && layout.position(*first) != layout.position(*second) {
+                    stats.same_finger += count;
+                }
+                if x.hand() != y.hand() {
+                    stats.alternating += count;
+                }
+            }
+        }
+        stats
+    }
+
+    /// One sample per second elapsed so far, the last one covering any
+    /// remaining fraction of a second. Each only counts the words submitted
+    /// within its own second, so that slowdowns stand out.
+    pub fn samples(&self) -> Vec<Sample> {
+        let duration = match self.duration() {
+            Ok(duration) => duration,
+            Err(_) => return Vec::new(),
+        };
+        let mut samples: Vec<Sample> = Vec::new();
+        let mut words = self.words.iter().peekable();
+        let mut second = 1;
+        while samples
+            .last()
+            .is_none_or(|sample| sample.elapsed < duration)
+        {
+            let elapsed = Duration::from_secs(second).min(duration);
+            let (mut attempted, mut correct, mut errors) = (0, 0, 0);
+            while let Some((_, is_correct)) = words.next_if(|(at, _)| *at <= elapsed) {
+                attempted += 1;
+                if *is_correct {
+                    correct += 1;
+                } else {
+                    errors += 1;
+                }
+            }
+            let seconds = (elapsed - Duration::from_secs(second - 1)).as_secs_f32();
+            let per_minute = |words: u32| match seconds > 0f32 {
+                true => words as f32 * 60f32 / seconds,
+                false => 0f32,
+            };
+            samples.push(Sample {
+                elapsed,
+                raw_wpm: per_minute(attempted),
+                net_wpm: per_minute(correct),
+                errors,
+            });
+            second += 1;
+        }
+        samples
     }
 }
 
 impl fmt::Display for PerformanceMonitor {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rolling = |secs| {
+            self.rolling_wpm(Duration::from_secs(secs))
+                .unwrap_or_default()
+        };
         write!(
             f,
-            "Correct: {}\nAttempted: {}\nAccuracy: {}\nWPM: {}\nDuration: {:?}",
+            "Correct: {}\nAttempted: {}\nAccuracy: {}\nWPM: {}\nWPM {}s/{}s: {:.0} / {:.0}\n\
+             Burst WPM: {:.0}\nConsistency: {:.2}\nDuration: {:?}\nMode: {}",
             self.correct(),
             self.attempted(),
             self.accuracy().unwrap_or_default(),
             self.wpm().unwrap_or_default(),
+            consts::SHORT_WINDOW_SECS,
+            consts::LONG_WINDOW_SECS,
+            rolling(consts::SHORT_WINDOW_SECS),
+            rolling(consts::LONG_WINDOW_SECS),
+            self.burst_wpm().unwrap_or_default(),
+            self.consistency().unwrap_or_default(),
             self.duration().unwrap_or_default(),
+            self.comparison,
+        )
+    }
+}
+
+/// The results of a session, as kept in the history. Fields added after
+/// the first history files default to zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub wpm: f32,
+    #[serde(default)]
+    pub raw_wpm: f32,
+    pub accuracy: f32,
+    #[serde(default)]
+    pub correct: u32,
+    #[serde(default)]
+    pub attempted: u32,
+    pub duration_ms: u64,
+}
+
+impl Summary {
+    pub fn errors(&self) -> u32 {
+        self.attempted.saturating_sub(self.correct)
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+}
+
+/// Accuracy and speed by finger and hand, and how the submitted words move
+/// between them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FingerStats {
+    pub fingers: BTreeMap<Finger, KeyStat>,
+    pub hands: BTreeMap<Hand, KeyStat>,
+    /// Pairs of consecutive letters typed by one finger on different keys.
+    pub same_finger: u32,
+    /// Pairs of consecutive letters typed by alternate hands.
+    pub alternating: u32,
+    /// Pairs of consecutive letters within words, both on the layout.
+    pub bigrams: u32,
+}
+
+impl FingerStats {
+    pub fn same_finger_rate(&self) -> Option<f32> {
+        match self.bigrams {
+            0 => None,
+            _ => Some(self.same_finger as f32 / self.bigrams as f32),
+        }
+    }
+
+    pub fn alternation_rate(&self) -> Option<f32> {
+        match self.bigrams {
+            0 => None,
+            _ => Some(self.alternating as f32 / self.bigrams as f32),
+        }
+    }
+}
+
+impl fmt::Display for FingerStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let row = |f: &mut fmt::Formatter, name: String, stat: &KeyStat| {
+            writeln!(
+                f,
+                "{:<12} {:>8.1}% {:>6}ms",
+                name,
+                stat.accuracy().unwrap_or_default() * 100f32,
+                stat.average_latency().unwrap_or_default().as_millis()
+            )
+        };
+        writeln!(f, "{:<12} {:>9} {:>8}", "", "Accuracy", "Latency")?;
+        for (finger, stat) in self.fingers.iter() {
+            row(f, finger.to_string(), stat)?;
+        }
+        for (hand, stat) in self.hands.iter() {
+            row(f, hand.to_string(), stat)?;
+        }
+        write!(
+            f,
+            "Same-finger bigrams: {:.1}%\nHand alternation: {:.1}%",
+            self.same_finger_rate().unwrap_or_default() * 100f32,
+            self.alternation_rate().unwrap_or_default() * 100f32
         )
     }
 }
@@ -112,3 +508,144 @@ impl error::Error for PerformanceMonitorError {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples() {
+        let start = Instant::now();
+        let mut performance = PerformanceMonitor::new();
+        assert!(performance.samples().is_empty());
+        performance.start_at(start).unwrap();
+        performance.register_at("the", "the", start + Duration::from_millis(500));
+        performance.register_at("teh", "the", start + Duration::from_millis(900));
+        performance.register_at("the", "the", start + Duration::from_millis(1500));
+        performance
+            .end_at(start + Duration::from_millis(2500))
+            .unwrap();
+
+        let samples = performance.samples();
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].elapsed, Duration::from_secs(1));
+        assert_eq!(samples[0].raw_wpm, 120f32);
+        assert_eq!(samples[0].net_wpm, 60f32);
+        assert_eq!(samples[0].errors, 1);
+        assert_eq!(samples[1].raw_wpm, 60f32);
+        assert_eq!(samples[1].net_wpm, 60f32);
+        assert_eq!(samples[1].errors, 0);
+        assert_eq!(samples[2].elapsed, Duration::from_millis(2500));
+        assert_eq!(samples[2].net_wpm, 0f32);
+
+        let summary = performance.summary();
+        assert_eq!(summary.wpm, 48f32);
+        assert_eq!(summary.raw_wpm, 72f32);
+        assert_eq!(summary.errors(), 1);
+        assert_eq!(summary.duration(), Duration::from_millis(2500));
+    }
+
+    #[test]
+    fn test_pause() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut performance = PerformanceMonitor::new();
+        assert!(performance.pause_at(start).is_err());
+        performance.start_at(start).unwrap();
+        performance.register_at("a", "a", at(1));
+        performance.pause_at(at(2)).unwrap();
+        assert!(performance.pause_at(at(3)).is_err());
+        assert_eq!(performance.elapsed_at(at(50)), Duration::from_secs(2));
+        assert_eq!(
+            performance.resume_at(at(10)).unwrap(),
+            Duration::from_secs(8)
+        );
+        assert!(performance.resume_at(at(11)).is_err());
+        performance.register_at("a", "a", at(11));
+        performance.pause_at(at(12)).unwrap();
+        performance.end_at(at(20)).unwrap();
+
+        assert!(!performance.is_paused());
+        assert_eq!(performance.duration().unwrap(), Duration::from_secs(4));
+        assert_eq!(performance.wpm().unwrap(), 30f32);
+        assert_eq!(performance.wpm_per_second(), vec![60f32, 0f32, 60f32, 0f32]);
+        assert!(performance.pause_at(at(21)).is_err());
+    }
+
+    #[test]
+    fn test_rolling_metrics() {
+        let start = Instant::now();
+        let mut performance = PerformanceMonitor::new();
+        assert!(performance.rolling_wpm(Duration::from_secs(5)).is_err());
+        assert!(performance.burst_wpm().is_err());
+        performance.start_at(start).unwrap();
+        for (millis, entered) in [
+            (500, "a"),
+            (900, "x"),
+            (1500, "a"),
+            (1750, "a"),
+            (3500, "a"),
+        ]
+        .iter()
+        {
+            performance.register_at(entered, "a", start + Duration::from_millis(*millis));
+        }
+        performance.end_at(start + Duration::from_secs(4)).unwrap();
+
+        assert_eq!(
+            performance.rolling_wpm(Duration::from_secs(1)).unwrap(),
+            60f32
+        );
+        assert_eq!(
+            performance.rolling_wpm(Duration::from_secs(10)).unwrap(),
+            60f32
+        );
+        assert_eq!(
+            performance.wpm_per_second(),
+            vec![60f32, 120f32, 0f32, 60f32]
+        );
+        assert!(
+            (performance.consistency().unwrap() - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-4
+        );
+    }
+
+    #[test]
+    fn test_burst_wpm() {
+        let mut performance = PerformanceMonitor::new();
+        assert!(performance.burst_wpm().is_err());
+        performance.register_word_time("hello", Duration::from_millis(500));
+        performance.register_word_time("it", Duration::from_millis(100));
+        performance.register_word_time("a", Duration::default());
+        assert_eq!(performance.burst_wpm().unwrap(), 240f32);
+    }
+
+    #[test]
+    fn test_finger_stats() {
+        let start = Instant::now();
+        let mut performance = PerformanceMonitor::new();
+        performance.start_at(start).unwrap();
+        for (expected, correct, latency) in
+            [('d', true, 100), ('e', false, 300), ('a', true, 200)].iter()
+        {
+            let latency = Some(Duration::from_millis(*latency));
+            performance.register_key(*expected, *correct, latency);
+        }
+        performance.register_at("dead", "dead", start);
+        performance.register_at("ow", "ow", start);
+
+        let stats = performance.finger_stats(&Layout::default());
+        let middle = stats.fingers[&Finger::LeftMiddle];
+        assert_eq!(middle.accuracy(), Some(0.5));
+        assert_eq!(middle.average_latency(), Some(Duration::from_millis(200)));
+        assert_eq!(stats.hands[&Hand::Left].presses, 3);
+        assert!(!stats.hands.contains_key(&Hand::Right));
+        // "de" is the same finger, "ea" and "ad" are not, "ow" alternates.
+        assert_eq!(stats.bigrams, 4);
+        assert_eq!(stats.same_finger, 1);
+        assert_eq!(stats.alternating, 1);
+        assert_eq!(stats.same_finger_rate(), Some(0.25));
+        assert!(stats
+            .to_string()
+            .ends_with("Same-finger bigrams: 25.0%\nHand alternation: 25.0%"));
+    }
+}
@@ -0,0 +1,270 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::script::Script;
+use crate::session::Keystroke;
+
+/// A previous run replayed at its original pace.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Ghost {
+    submissions: Vec<Duration>,
+}
+
+impl Ghost {
+    pub fn from_script(script: &Script) -> Ghost {
+        let submissions = script
+            .events()
+            .iter()
+            .filter(|(_, keystroke)| match keystroke {
+                Keystroke::Char(c) => c.is_whitespace(),
+                Keystroke::Backspace => false,
+            })
+            .map(|(offset, _)| *offset)
+            .collect();
+        Ghost { submissions }
+    }
+
+    /// The number of words the ghost had submitted after `elapsed`.
+    pub fn position(&self, elapsed: Duration) -> usize {
+        self.submissions
+            .partition_point(|offset| *offset <= elapsed)
+    }
+}
+
+/// The fastest pace a pacer types at.
+pub const MAX_PACER_WPM: f64 = 1000.0;
+
+/// A bot that submits words at a steady pace. Each word takes as long as it
+/// would at a speed drawn between the minimum and maximum WPM.
+#[derive(Clone, Debug)]
+pub struct Pacer {
+    min_wpm: f64,
+    max_wpm: f64,
+    rng: StdRng,
+    submissions: Vec<Duration>,
+}
+
+impl Pacer {
+    pub fn new(wpm: f64, rng: StdRng) -> Pacer {
+        Pacer {
+            min_wpm: wpm,
+            max_wpm: wpm,
+            rng,
+            submissions: Vec::new(),
+        }
+    }
+
+    /// Varies the pace of each word between the initial WPM and `max_wpm`.
+    pub fn with_max_wpm(mut self, max_wpm: f64) -> Pacer {
+        self.max_wpm = max_wpm;
+        self
+    }
+
+    /// A speed for the next word, kept finite and at most `MAX_PACER_WPM`
+    /// so that every word takes some time.
+    fn next_wpm(&mut self) -> f64 {
+        let wpm = if self.max_wpm > self.min_wpm {
+            self.rng.gen_range(self.min_wpm, self.max_wpm)
+        } else {
+            self.min_wpm
+        };
+        match wpm.is_finite() {
+            true => wpm.min(MAX_PACER_WPM),
+            false => MAX_PACER_WPM,
+        }
+    }
+
+    /// Submits every word the pacer would have typed by `elapsed`.
+    pub fn advance(&mut self, elapsed: Duration) {
+        while self.submissions.last().is_none_or(|last| *last <= elapsed) {
+            let last = self.submissions.last().copied().unwrap_or_default();
+            let word = Duration::from_secs_f64(60.0 / self.next_wpm());
+            self.submissions.push(last + word);
+        }
+    }
+
+    /// The number of words the pacer had submitted after `elapsed`, as far
+    /// as it has been advanced.
+    pub fn position(&self, elapsed: Duration) -> usize {
+        self.submissions
+            .partition_point(|offset| *offset <= elapsed)
+    }
+}
+
+impl fmt::Display for Pacer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.max_wpm > self.min_wpm {
+            write!(f, "{}-{} wpm", self.min_wpm, self.max_wpm)
+        } else {
+            write!(f, "{} wpm", self.min_wpm)
+        }
+    }
+}
+
+/// How far another player in a multiplayer race has got.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlayerProgress {
+    pub words: usize,
+    pub wpm: f32,
+    pub finished: bool,
+}
+
+impl fmt::Display for PlayerProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({:.0} wpm)", self.words, self.wpm)?;
+        if self.finished {
+            write!(f, " done")?;
+        }
+        Ok(())
+    }
+}
+
+/// The latest progress of every other player, by name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scoreboard {
+    players: BTreeMap<String, PlayerProgress>,
+}
+
+impl Scoreboard {
+    pub fn new() -> Scoreboard {
+        Scoreboard::default()
+    }
+
+    pub fn update(&mut self, name: &str, progress: PlayerProgress) {
+        self.players.insert(name.to_owned(), progress);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.players.remove(name);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.players.contains_key(name)
+    }
+
+    pub fn players(&self) -> Vec<(String, PlayerProgress)> {
+        self.players
+            .iter()
+            .map(|(name, progress)| (name.clone(), *progress))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Won,
+    Lost,
+    Drawn,
+}
+
+impl Outcome {
+    pub fn of(progress: usize, position: usize) -> Outcome {
+        match progress.cmp(&position) {
+            Ordering::Greater => Outcome::Won,
+            Ordering::Less => Outcome::Lost,
+            Ordering::Equal => Outcome::Drawn,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Won => write!(f, "you win"),
+            Outcome::Lost => write!(f, "you lose"),
+            Outcome::Drawn => write!(f, "draw"),
+        }
+    }
+}
+
+/// Describes how far `progress` is from a rival's `position`, in words.
+pub fn standing(progress: usize, position: usize) -> String {
+    match progress.cmp(&position) {
+        Ordering::Greater => format!("Ahead by {}", progress - position),
+        Ordering::Less => format!("Behind by {}", position - progress),
+        Ordering::Equal => "Level".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_ghost_position() {
+        let script = "0 o\n100 f\n200 space\n300 x\n400 backspace\n500 space\n";
+        let ghost = Ghost::from_script(&Script::parse(&mut script.as_bytes()).unwrap());
+        assert_eq!(ghost.position(Duration::from_millis(0)), 0);
+        assert_eq!(ghost.position(Duration::from_millis(200)), 1);
+        assert_eq!(ghost.position(Duration::from_millis(499)), 1);
+        assert_eq!(ghost.position(Duration::from_secs(10)), 2);
+    }
+
+    #[test]
+    fn test_pacer_position() {
+        let mut pacer = Pacer::new(60.0, StdRng::seed_from_u64(0));
+        assert_eq!(pacer.position(Duration::from_secs(10)), 0);
+        pacer.advance(Duration::from_millis(2500));
+        assert_eq!(pacer.position(Duration::from_millis(999)), 0);
+        assert_eq!(pacer.position(Duration::from_millis(2500)), 2);
+        pacer.advance(Duration::from_secs(10));
+        assert_eq!(pacer.position(Duration::from_secs(10)), 10);
+        assert_eq!(pacer.to_string(), "60 wpm");
+    }
+
+    #[test]
+    fn test_varying_pacer() {
+        let mut pacer = Pacer::new(30.0, StdRng::seed_from_u64(0)).with_max_wpm(120.0);
+        pacer.advance(Duration::from_secs(60));
+        let position = pacer.position(Duration::from_secs(60));
+        assert!((30..=120).contains(&position));
+        assert_eq!(pacer.to_string(), "30-120 wpm");
+    }
+
+    #[test]
+    fn test_pacer_too_fast() {
+        for wpm in [f64::INFINITY, f64::MAX, 1e300].iter() {
+            let mut pacer = Pacer::new(*wpm, StdRng::seed_from_u64(0));
+            pacer.advance(Duration::from_secs(60));
+            assert_eq!(
+                pacer.position(Duration::from_secs(60)),
+                MAX_PACER_WPM as usize
+            );
+        }
+    }
+
+    #[test]
+    fn test_scoreboard() {
+        let mut scoreboard = Scoreboard::new();
+        scoreboard.update("bob", PlayerProgress::default());
+        let alice = PlayerProgress {
+            words: 3,
+            wpm: 42.4,
+            finished: true,
+        };
+        scoreboard.update("alice", alice);
+        assert!(scoreboard.contains("bob"));
+        assert_eq!(scoreboard.players()[0], ("alice".to_string(), alice));
+        assert_eq!(alice.to_string(), "3 (42 wpm) done");
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(Outcome::of(3, 1), Outcome::Won);
+        assert_eq!(Outcome::of(1, 3), Outcome::Lost);
+        assert_eq!(Outcome::of(2, 2), Outcome::Drawn);
+    }
+
+    #[test]
+    fn test_standing() {
+        assert_eq!(standing(3, 1), "Ahead by 2");
+        assert_eq!(standing(1, 3), "Behind by 2");
+        assert_eq!(standing(2, 2), "Level");
+    }
+}
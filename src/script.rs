@@ -0,0 +1,303 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::BufRead;
+use std::time::{Duration, Instant};
+
+use crate::performance::PerformanceMonitorError;
+use crate::session::{Keystroke, Session};
+
+const SPACE: &str = "space";
+const BACKSPACE: &str = "backspace";
+const WORDS: &str = "words";
+const SEED: &str = "seed";
+
+/// A timestamped list of keystrokes, optionally with the words they were
+/// typed against.
+///
+/// Each line holds a keystroke as milliseconds since the start followed by
+/// a character, `space` or `backspace`. Other whitespace is escaped as by
+/// `char::escape_default`, e.g. `\t`. Lines starting with `words` list
+/// the expected words, a `seed` line records the seed the words were drawn
+/// with, and lines starting with `#` are comments:
+///
+/// ```text
+/// seed 42
+/// words sphinx of
+/// 0 s
+/// 150 x
+/// 300 backspace
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Script {
+    seed: Option<u64>,
+    words: Vec<String>,
+    events: Vec<(Duration, Keystroke)>,
+}
+
+impl Script {
+    pub fn new() -> Script {
+        Script::default()
+    }
+
+    pub fn parse<R: BufRead>(reader: &mut R) -> Result<Script, ScriptError> {
+        let mut script = Script::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parse_error = |message: &str| ScriptError::Parse {
+                line: i + 1,
+                message: message.to_owned(),
+            };
+            let mut fields = line.split_whitespace();
+            let head = fields.next().unwrap();
+            if head == WORDS {
+                script.words.extend(fields.map(String::from));
+                continue;
+            }
+            if head == SEED {
+                let seed = fields.next().and_then(|seed| seed.parse().ok());
+                if seed.is_none() || fields.next().is_some() {
+                    return Err(parse_error("Expected a single numeric seed."));
+                }
+                script.seed = seed;
+                continue;
+            }
+            let millis = head
+                .parse::<u64>()
+                .map_err(|_| parse_error("Expected a timestamp in milliseconds."))?;
+            let keystroke = match fields.next() {
+                Some(SPACE) => Some(Keystroke::Char(' ')),
+                Some(BACKSPACE) => Some(Keystroke::Backspace),
+                Some(key) => key
+                    .parse()
+                    .ok()
+                    .or_else(|| unescape(key))
+                    .map(Keystroke::Char),
+                None => None,
+            }
+            .ok_or_else(|| parse_error("Expected a character, space or backspace."))?;
+            if fields.next().is_some() {
+                return Err(parse_error("Unexpected trailing input."));
+            }
+            script.push(Duration::from_millis(millis), keystroke);
+            if let [.., (previous, _), (current, _)] = script.events.as_slice() {
+                if current < previous {
+                    return Err(parse_error("Timestamps must not decrease."));
+                }
+            }
+        }
+        Ok(script)
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Script {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn words(&self) -> &[String] {
+        &self.words
+    }
+
+    pub fn events(&self) -> &[(Duration, Keystroke)] {
+        &self.events
+    }
+
+    pub fn with_words(mut self, words: Vec<String>) -> Script {
+        self.words = words;
+        self
+    }
+
+    pub fn push(&mut self, offset: Duration, keystroke: Keystroke) {
+        self.events.push((offset, keystroke));
+    }
+
+    /// The offset of the last keystroke.
+    pub fn duration(&self) -> Duration {
+        self.events
+            .last()
+            .map_or_else(Duration::default, |(offset, _)| *offset)
+    }
+
+    /// Plays every keystroke into `session`, starting it at `start` and
+    /// ending it at the time of the last keystroke.
+    pub fn run(&self, session: &mut Session, start: Instant) -> Result<(), ScriptError> {
+        session.start(start)?;
+        for (offset, keystroke) in self.events.iter() {
+            session.press(*keystroke, start + *offset);
+        }
+        session.end(start + self.duration())?;
+        Ok(())
+    }
+}
+
+/// The character escaped as `key` by `char::escape_default`, if any.
+fn unescape(key: &str) -> Option<char> {
+    match key {
+        "\\t" => Some('\t'),
+        "\\n" => Some('\n'),
+        "\\r" => Some('\r'),
+        _ => {
+            let hex = key.strip_prefix("\\u{")?.strip_suffix('}')?;
+            char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+        }
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(seed) = self.seed {
+            writeln!(f, "{} {}", SEED, seed)?;
+        }
+        if !self.words.is_empty() {
+            writeln!(f, "{} {}", WORDS, self.words.join(" "))?;
+        }
+        for (offset, keystroke) in self.events.iter() {
+            let millis = offset.as_millis();
+            match keystroke {
+                Keystroke::Char(' ') => writeln!(f, "{} {}", millis, SPACE)?,
+                Keystroke::Char(c) if c.is_whitespace() => {
+                    writeln!(f, "{} {}", millis, c.escape_default())?
+                }
+                Keystroke::Char(c) => writeln!(f, "{} {}", millis, c)?,
+                Keystroke::Backspace => writeln!(f, "{} {}", millis, BACKSPACE)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    Performance(PerformanceMonitorError),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::Io(err) => write!(f, "Could not read script: {}", err),
+            ScriptError::Parse { line, message } => {
+                write!(f, "Invalid script on line {}: {}", line, message)
+            }
+            ScriptError::Performance(err) => write!(f, "Could not run script: {}", err),
+        }
+    }
+}
+
+impl error::Error for ScriptError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ScriptError::Io(err) => Some(err),
+            ScriptError::Parse { .. } => None,
+            ScriptError::Performance(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for ScriptError {
+    fn from(err: io::Error) -> ScriptError {
+        ScriptError::Io(err)
+    }
+}
+
+impl From<PerformanceMonitorError> for ScriptError {
+    fn from(err: PerformanceMonitorError) -> ScriptError {
+        ScriptError::Performance(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ModelBuilder;
+    use crate::performance::PerformanceMonitor;
+
+    const SCRIPT: &str = "# sphinx of black\n\
+                          seed 42\n\
+                          words sphinx of\n\
+                          words black\n\
+                          0 s\n\
+                          100 p\n\
+                          200 h\n\
+                          300 i\n\
+                          400 n\n\
+                          500 x\n\
+                          600 space\n\
+                          700 o\n\
+                          800 g\n\
+                          900 backspace\n\
+                          1000 f\n\
+                          1100 space\n\
+                          1200 b\n\
+                          1300 l\n\
+                          1400 space\n";
+
+    #[test]
+    fn test_parse() {
+        let script = Script::parse(&mut SCRIPT.as_bytes()).unwrap();
+        assert_eq!(script.seed(), Some(42));
+        assert_eq!(script.words(), ["sphinx", "of", "black"]);
+        assert_eq!(script.events().len(), 15);
+        assert_eq!(
+            script.events()[9],
+            (Duration::from_millis(900), Keystroke::Backspace)
+        );
+        assert_eq!(script.duration(), Duration::from_millis(1400));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let script = Script::parse(&mut SCRIPT.as_bytes()).unwrap();
+        let written = script.to_string();
+        assert_eq!(Script::parse(&mut written.as_bytes()).unwrap(), script);
+
+        let mut script = Script::new();
+        for (millis, c) in [(0, '\t'), (10, '\u{a0}'), (20, '\\'), (30, ' ')].iter() {
+            script.push(Duration::from_millis(*millis), Keystroke::Char(*c));
+        }
+        let written = script.to_string();
+        assert_eq!(written, "0 \\t\n10 \\u{a0}\n20 \\\n30 space\n");
+        assert_eq!(Script::parse(&mut written.as_bytes()).unwrap(), script);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (text, line) in [
+            ("0 s\nx s\n", 2),
+            ("0 sp\n", 1),
+            ("10 s\n5 p\n", 2),
+            ("seed x\n", 1),
+            ("0 \\x\n", 1),
+        ]
+        .iter()
+        {
+            match Script::parse(&mut text.as_bytes()) {
+                Err(ScriptError::Parse { line: actual, .. }) => assert_eq!(actual, *line),
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_run() {
+        let script = Script::parse(&mut SCRIPT.as_bytes()).unwrap();
+        let stream = script.words().to_vec().into_iter();
+        let model = ModelBuilder::new().with_word_stream(stream).build();
+        let mut session = Session::new(model, PerformanceMonitor::new());
+        script.run(&mut session, Instant::now()).unwrap();
+        let performance = session.performance();
+        assert_eq!(performance.correct(), 2);
+        assert_eq!(performance.attempted(), 3);
+        assert_eq!(performance.duration().unwrap(), Duration::from_millis(1400));
+        assert!((performance.wpm().unwrap() - 2.0 / 1.4 * 60.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,714 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::compare::Comparison;
+use crate::history::{Category, PersonalBests, Variant};
+use crate::keyboard::{KeyStats, Keymap};
+use crate::model::Model;
+use crate::performance::{PerformanceMonitor, PerformanceMonitorError, Summary};
+use crate::race::{Ghost, Pacer, PlayerProgress, Scoreboard};
+use crate::script::Script;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Keystroke {
+    Char(char),
+    Backspace,
+}
+
+impl Keystroke {
+    /// Infers the keystrokes that turn the entry `before` into `after`, as
+    /// reported by a text box that only exposes its full contents.
+    pub fn between(before: &str, after: &str) -> Vec<Keystroke> {
+        let common = before
+            .chars()
+            .zip(after.chars())
+            .take_while(|(x, y)| x == y)
+            .count();
+        let removed = before.chars().count() - common;
+        let added = after.chars().skip(common).map(Keystroke::Char);
+        std::iter::repeat_n(Keystroke::Backspace, removed)
+            .chain(added)
+            .collect()
+    }
+}
+
+/// A word submitted during a session.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Submission {
+    /// The position of the word in the whole session.
+    pub index: usize,
+    pub expected: String,
+    pub entered: String,
+    pub correct: bool,
+    /// Time since the session started.
+    pub elapsed: Duration,
+    /// Time from the first keystroke of the word to its submission.
+    pub duration: Duration,
+}
+
+/// Told about every word submitted in a session.
+pub trait Observer: fmt::Debug + Send + Sync {
+    fn on_submission(&mut self, submission: &Submission);
+}
+
+/// Everything a front-end needs to draw the words panel.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub words: Vec<String>,
+    pub history: Vec<String>,
+    pub entry: String,
+    pub comparison: Comparison,
+    /// The number of words before `words[0]` in the whole session.
+    pub offset: usize,
+    /// How many words the ghost has typed, if racing one.
+    pub ghost: Option<usize>,
+    /// How many words the pacer has typed, if racing one.
+    pub pacer: Option<usize>,
+    /// Every other player in a multiplayer race.
+    pub players: Vec<(String, PlayerProgress)>,
+}
+
+impl Snapshot {
+    /// The number of words the user has typed.
+    pub fn progress(&self) -> usize {
+        self.offset + self.history.len()
+    }
+}
+
+/// A typing session independent of any user interface. Front-ends feed it
+/// keystrokes and draw its snapshots.
+#[derive(Debug)]
+pub struct Session {
+    model: Model,
+    performance: PerformanceMonitor,
+    entry: String,
+    word_started: Option<Instant>,
+    submissions: Vec<Submission>,
+    last_keystroke: Option<Instant>,
+    /// The last keystroke, start or resumption.
+    last_activity: Option<Instant>,
+    auto_pause: Option<Duration>,
+    keymap: Option<Keymap>,
+    recording: Option<Script>,
+    ghost: Option<Ghost>,
+    pacer: Option<Pacer>,
+    scoreboard: Option<Arc<Mutex<Scoreboard>>>,
+    personal_bests: Option<PersonalBests>,
+    /// The speed to beat, chosen when the session starts.
+    personal_best: Option<Summary>,
+    /// How the words are drawn; rivals are added by `variant`.
+    variant: Variant,
+    observers: Vec<Box<dyn Observer>>,
+}
+
+impl Session {
+    pub fn new(model: Model, performance: PerformanceMonitor) -> Session {
+        Session {
+            model,
+            performance,
+            entry: String::new(),
+            word_started: None,
+            submissions: Vec::new(),
+            last_keystroke: None,
+            last_activity: None,
+            auto_pause: None,
+            keymap: None,
+            recording: None,
+            ghost: None,
+            pacer: None,
+            scoreboard: None,
+            personal_bests: None,
+            personal_best: None,
+            variant: Variant::default(),
+            observers: Vec::new(),
+        }
+    }
+
+    pub fn with_ghost(mut self, ghost: Ghost) -> Session {
+        self.ghost = Some(ghost);
+        self
+    }
+
+    pub fn with_pacer(mut self, pacer: Pacer) -> Session {
+        self.pacer = Some(pacer);
+        self
+    }
+
+    pub fn pacer(&self) -> Option<&Pacer> {
+        self.pacer.as_ref()
+    }
+
+    /// Shows the progress of other players, kept up to date by whatever
+    /// connects them.
+    pub fn with_scoreboard(mut self, scoreboard: Arc<Mutex<Scoreboard>>) -> Session {
+        self.scoreboard = Some(scoreboard);
+        self
+    }
+
+    pub fn has_rivals(&self) -> bool {
+        self.ghost.is_some() || self.pacer.is_some() || self.scoreboard.is_some()
+    }
+
+    /// Tells the session how its words are drawn, so that it is only compared
+    /// with sessions drawing them the same way.
+    pub fn with_variant(mut self, variant: Variant) -> Session {
+        self.variant = variant;
+        self
+    }
+
+    /// What sets this session apart from others of the same mode and source.
+    pub fn variant(&self) -> Variant {
+        Variant {
+            pacer: self.pacer.as_ref().map(Pacer::to_string),
+            ghost: self.ghost.is_some(),
+            race: self.scoreboard.is_some(),
+            ..self.variant.clone()
+        }
+    }
+
+    /// The category of this session were it to last `duration`.
+    pub fn category(&self, duration: Duration) -> Category {
+        Category::new(
+            &self.performance.comparison().to_string(),
+            self.model.source().name(),
+            &self.variant(),
+            duration,
+        )
+    }
+
+    /// Compares the session with the fastest earlier ones.
+    pub fn with_personal_bests(mut self, personal_bests: PersonalBests) -> Session {
+        self.personal_bests = Some(personal_bests);
+        self
+    }
+
+    pub fn personal_bests(&self) -> Option<&PersonalBests> {
+        self.personal_bests.as_ref()
+    }
+
+    /// The fastest earlier session like this one of any length, chosen when
+    /// the session starts so that it holds still while typing.
+    pub fn personal_best(&self) -> Option<&Summary> {
+        self.personal_best.as_ref()
+    }
+
+    /// Moves the pacer up to `now`. Ghosts follow their recording and need
+    /// no advancing.
+    pub fn advance(&mut self, now: Instant) {
+        let elapsed = self.elapsed(now);
+        if let Some(pacer) = &mut self.pacer {
+            pacer.advance(elapsed);
+        }
+    }
+
+    /// Remaps the characters typed through `input`, to simulate another
+    /// layout. Keystrokes given to `press` are taken as they are.
+    pub fn with_keymap(mut self, keymap: Keymap) -> Session {
+        self.keymap = Some(keymap);
+        self
+    }
+
+    /// Pauses the session when nothing has been typed for `idle`.
+    pub fn with_auto_pause(mut self, idle: Duration) -> Session {
+        self.auto_pause = Some(idle);
+        self
+    }
+
+    pub fn add_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    /// Keeps every keystroke pressed after the session starts.
+    pub fn with_recording(mut self) -> Session {
+        self.recording = Some(Script::new());
+        self
+    }
+
+    pub fn recording(&self) -> Option<&Script> {
+        self.recording.as_ref()
+    }
+
+    /// The time in the session at `Instant::now()`, which differs from it
+    /// when replaying at another speed.
+    pub fn now(&self) -> Instant {
+        self.performance.now()
+    }
+
+    pub fn start(&mut self, now: Instant) -> Result<(), PerformanceMonitorError> {
+        self.performance.start_at(now)?;
+        self.last_activity = Some(now);
+        let category = self.category(Duration::default());
+        self.personal_best = self
+            .personal_bests
+            .as_ref()
+            .and_then(|bests| bests.fastest(&category))
+            .copied();
+        Ok(())
+    }
+
+    /// Stops the clock, and with it any ghost or pacer.
+    pub fn pause(&mut self, now: Instant) -> Result<(), PerformanceMonitorError> {
+        self.performance.pause_at(now)
+    }
+
+    /// Restarts the clock. The word being typed and the latency of the
+    /// next keystroke are timed as if there had been no pause.
+    pub fn resume(&mut self, now: Instant) -> Result<(), PerformanceMonitorError> {
+        let paused = self.performance.resume_at(now)?;
+        self.word_started = self.word_started.map(|started| started + paused);
+        self.last_keystroke = self.last_keystroke.map(|last| last + paused);
+        self.last_activity = Some(now);
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.performance.is_paused()
+    }
+
+    /// Pauses the session if auto-pause is on and nothing has been typed
+    /// for long enough, returning whether it did.
+    pub fn auto_pause(&mut self, now: Instant) -> bool {
+        match (self.auto_pause, self.last_activity) {
+            (Some(idle), Some(last)) if now.saturating_duration_since(last) >= idle => {
+                self.pause(now).is_ok()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn end(&mut self, now: Instant) -> Result<(), PerformanceMonitorError> {
+        self.performance.end_at(now)?;
+        self.advance(now);
+        Ok(())
+    }
+
+    pub fn press(&mut self, keystroke: Keystroke, now: Instant) {
+        if self.performance.is_started() {
+            let elapsed = self.elapsed(now);
+            if let Some(recording) = &mut self.recording {
+                recording.push(elapsed, keystroke);
+            }
+        }
+        match keystroke {
+            Keystroke::Char(c) if c.is_whitespace() => self.submit(now),
+            Keystroke::Char(c) => {
+                self.word_started.get_or_insert(now);
+                self.record_key(c, now);
+                self.entry.push(c);
+            }
+            Keystroke::Backspace => {
+                self.entry.pop();
+            }
+        }
+        if self.performance.is_started() {
+            self.last_keystroke = Some(now);
+            self.last_activity = Some(now);
+        }
+    }
+
+    /// Counts `c` against the character expected where it is typed.
+    fn record_key(&mut self, c: char, now: Instant) {
+        if !self.performance.is_started() {
+            return;
+        }
+        let position = self.entry.chars().count();
+        let expected = self
+            .model
+            .get_current_word()
+            .and_then(|word| word.chars().nth(position));
+        if let Some(expected) = expected {
+            let correct = self
+                .performance
+                .comparison()
+                .words_eq(&c.to_string(), &expected.to_string());
+            let latency = self
+                .last_keystroke
+                .map(|last| now.saturating_duration_since(last));
+            self.performance.register_key(expected, correct, latency);
+        }
+    }
+
+    /// Applies whatever keystrokes turn the current entry into `contents`.
+    pub fn input(&mut self, contents: &str, now: Instant) {
+        for keystroke in Keystroke::between(&self.entry, contents) {
+            let keystroke = match (keystroke, &self.keymap) {
+                (Keystroke::Char(c), Some(keymap)) => Keystroke::Char(keymap.map(c)),
+                (keystroke, _) => keystroke,
+            };
+            self.press(keystroke, now);
+        }
+    }
+
+    fn submit(&mut self, now: Instant) {
+        let entered = self.entry.trim().to_owned();
+        if let Some(expected) = self.model.get_current_word() {
+            let submission = Submission {
+                index: self.model.get_offset() + self.model.get_history().len(),
+                correct: self.performance.comparison().words_eq(&entered, &expected),
+                elapsed: self.elapsed(now),
+                duration: self.word_started.map_or_else(Duration::default, |started| {
+                    now.saturating_duration_since(started)
+                }),
+                expected,
+                entered,
+            };
+            self.model.register(&submission.entered);
+            self.performance
+                .register_at(&submission.entered, &submission.expected, now);
+            if submission.correct {
+                self.performance
+                    .register_word_time(&submission.expected, submission.duration);
+            }
+            for observer in self.observers.iter_mut() {
+                observer.on_submission(&submission);
+            }
+            self.submissions.push(submission);
+        }
+        self.entry.clear();
+        self.word_started = None;
+    }
+
+    /// Every word submitted so far, unlike the model's history which only
+    /// keeps the current row.
+    pub fn submissions(&self) -> &[Submission] {
+        &self.submissions
+    }
+
+    /// Statistics for every key pressed since the session started.
+    pub fn keys(&self) -> &KeyStats {
+        self.performance.keys()
+    }
+
+    pub fn entry(&self) -> &str {
+        &self.entry
+    }
+
+    pub fn model(&self) -> &Model {
+        &self.model
+    }
+
+    pub fn model_mut(&mut self) -> &mut Model {
+        &mut self.model
+    }
+
+    pub fn performance(&self) -> &PerformanceMonitor {
+        &self.performance
+    }
+
+    /// Time since the session started, leaving out pauses and frozen once
+    /// it has ended.
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        self.performance.elapsed_at(now)
+    }
+
+    pub fn snapshot(&self, now: Instant) -> Snapshot {
+        let elapsed = self.elapsed(now);
+        Snapshot {
+            words: self.model.get_words(),
+            history: self.model.get_history(),
+            entry: self.entry.clone(),
+            comparison: *self.performance.comparison(),
+            offset: self.model.get_offset(),
+            ghost: self.ghost.as_ref().map(|ghost| ghost.position(elapsed)),
+            pacer: self.pacer.as_ref().map(|pacer| pacer.position(elapsed)),
+            players: self
+                .scoreboard
+                .as_ref()
+                .map_or_else(Vec::new, |scoreboard| scoreboard.lock().unwrap().players()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Record;
+    use crate::keyboard::Layout;
+    use crate::model::ModelBuilder;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::thread;
+    use std::time::SystemTime;
+
+    fn session() -> Session {
+        let lexicon = vec!["sphinx", "of", "black", "quartz"];
+        let stream = lexicon.into_iter().map(String::from).cycle();
+        let model = ModelBuilder::new().with_word_stream(stream).build();
+        Session::new(model, PerformanceMonitor::new())
+    }
+
+    fn type_text(session: &mut Session, text: &str) {
+        for c in text.chars() {
+            session.press(Keystroke::Char(c), Instant::now());
+        }
+    }
+
+    #[test]
+    fn test_keystrokes_between() {
+        assert_eq!(Keystroke::between("sp", "sph"), vec![Keystroke::Char('h')]);
+        assert_eq!(Keystroke::between("sph", "sp"), vec![Keystroke::Backspace]);
+        assert_eq!(
+            Keystroke::between("spx", "sph"),
+            vec![Keystroke::Backspace, Keystroke::Char('h')]
+        );
+        assert_eq!(
+            Keystroke::between("café", "caf"),
+            vec![Keystroke::Backspace]
+        );
+    }
+
+    #[test]
+    fn test_typing() {
+        let mut session = session();
+        session.start(Instant::now()).unwrap();
+        type_text(&mut session, "sphinx ofx");
+        session.press(Keystroke::Backspace, Instant::now());
+        type_text(&mut session, " blk");
+        let snapshot = session.snapshot(Instant::now());
+        assert_eq!(snapshot.history, vec!["sphinx", "of"]);
+        assert_eq!(snapshot.entry, "blk");
+        assert_eq!(session.performance().correct(), 2);
+        assert_eq!(session.performance().attempted(), 2);
+        type_text(&mut session, " ");
+        assert_eq!(session.performance().correct(), 2);
+        assert_eq!(session.performance().attempted(), 3);
+        assert_eq!(
+            session.model().get_current_word(),
+            Some("quartz".to_string())
+        );
+    }
+
+    #[test]
+    fn test_input() {
+        let mut session = session();
+        let now = Instant::now();
+        session.input("sphinx", now);
+        session.input("sphinx ", now);
+        session.input("og", now);
+        session.input("o", now);
+        session.input("of ", now);
+        assert_eq!(session.entry(), "");
+        assert_eq!(session.performance().correct(), 2);
+    }
+
+    #[test]
+    fn test_keymap() {
+        let qwerty = Layout::default();
+        let dvorak = Layout::built_in("dvorak").unwrap();
+        let mut session = session().with_keymap(Keymap::between(&qwerty, &dvorak));
+        let start = Instant::now();
+        session.start(start).unwrap();
+        session.input(";", start);
+        assert_eq!(session.entry(), "s");
+        session.input("sr", start);
+        assert_eq!(session.entry(), "sp");
+        session.input("spjglb ", start);
+        assert_eq!(session.performance().correct(), 1);
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut session = session();
+        let start = Instant::now();
+        session.press(Keystroke::Char('x'), start);
+        session.press(Keystroke::Backspace, start);
+        session.start(start).unwrap();
+        session.input("sp", start + Duration::from_millis(100));
+        session.input("sph", start + Duration::from_millis(300));
+        session.input("spy", start + Duration::from_millis(400));
+        let keys = session.keys();
+        assert_eq!(keys.get('x'), None);
+        assert_eq!(keys.get('s').unwrap().timed, 0);
+        assert_eq!(
+            keys.get('p').unwrap().average_latency(),
+            Some(Duration::from_millis(0))
+        );
+        let h = keys.get('h').unwrap();
+        assert_eq!(h.presses, 2);
+        assert_eq!(h.errors, 1);
+        assert_eq!(h.average_latency(), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut session = session().with_recording();
+        let start = Instant::now();
+        session.press(Keystroke::Char('x'), start);
+        session.start(start).unwrap();
+        session.press(Keystroke::Char('s'), start + Duration::from_millis(10));
+        session.press(Keystroke::Backspace, start + Duration::from_millis(20));
+        assert_eq!(
+            session.recording().unwrap().events(),
+            [
+                (Duration::from_millis(10), Keystroke::Char('s')),
+                (Duration::from_millis(20), Keystroke::Backspace),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_ghost() {
+        let script = "0 s\n100 space\n200 o\n300 space\n";
+        let script = Script::parse(&mut script.as_bytes()).unwrap();
+        let mut session = session().with_ghost(Ghost::from_script(&script));
+        let start = Instant::now();
+        assert_eq!(session.snapshot(start).ghost, Some(0));
+        session.start(start).unwrap();
+        let snapshot = session.snapshot(start + Duration::from_millis(150));
+        assert_eq!(snapshot.ghost, Some(1));
+        assert_eq!(snapshot.progress(), 0);
+        session.end(start + Duration::from_millis(200)).unwrap();
+        let snapshot = session.snapshot(start + Duration::from_secs(10));
+        assert_eq!(snapshot.ghost, Some(1));
+    }
+
+    #[derive(Debug)]
+    struct Collector(Arc<Mutex<Vec<Submission>>>);
+
+    impl Observer for Collector {
+        fn on_submission(&mut self, submission: &Submission) {
+            self.0.lock().unwrap().push(submission.clone());
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        let submissions = Arc::new(Mutex::new(Vec::new()));
+        let collector = Collector(submissions.clone());
+        let mut session = session();
+        session.add_observer(Box::new(collector));
+        let start = Instant::now();
+        session.start(start).unwrap();
+        session.input("sphinx", start);
+        session.input("sphinx ", start + Duration::from_millis(800));
+        session.input("o", start + Duration::from_millis(850));
+        session.input("og ", start + Duration::from_millis(900));
+        assert_eq!(session.submissions(), &submissions.lock().unwrap()[..]);
+        assert_eq!(
+            *submissions.lock().unwrap(),
+            vec![
+                Submission {
+                    index: 0,
+                    expected: "sphinx".to_string(),
+                    entered: "sphinx".to_string(),
+                    correct: true,
+                    elapsed: Duration::from_millis(800),
+                    duration: Duration::from_millis(800),
+                },
+                Submission {
+                    index: 1,
+                    expected: "of".to_string(),
+                    entered: "og".to_string(),
+                    correct: false,
+                    elapsed: Duration::from_millis(900),
+                    duration: Duration::from_millis(50),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pacer() {
+        let pacer = Pacer::new(60.0, StdRng::seed_from_u64(0));
+        let mut session = session().with_pacer(pacer);
+        let start = Instant::now();
+        session.start(start).unwrap();
+        assert_eq!(session.snapshot(start).pacer, Some(0));
+        session.advance(start + Duration::from_millis(1500));
+        assert_eq!(
+            session.snapshot(start + Duration::from_millis(1500)).pacer,
+            Some(1)
+        );
+        session.end(start + Duration::from_secs(3)).unwrap();
+        let snapshot = session.snapshot(start + Duration::from_secs(10));
+        assert_eq!(snapshot.pacer, Some(3));
+    }
+
+    #[test]
+    fn test_replay_speed() {
+        let lexicon = vec!["sphinx", "of"];
+        let stream = lexicon.into_iter().map(String::from).cycle();
+        let model = ModelBuilder::new().with_word_stream(stream).build();
+        let mut session = Session::new(model, PerformanceMonitor::new().with_speed(2.0));
+        let before = Instant::now();
+        session.start(session.now()).unwrap();
+        let start = session.performance().started_at().unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let elapsed = session.now() - start;
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed <= before.elapsed() * 2);
+
+        // Keystrokes are stamped with the time they were recorded at.
+        for (offset, c) in "sphinx of ".chars().enumerate() {
+            let at = start + Duration::from_millis(100 * offset as u64);
+            session.press(Keystroke::Char(c), at);
+        }
+        session.end(start + Duration::from_secs(1)).unwrap();
+        assert_eq!(
+            session.performance().duration().unwrap(),
+            Duration::from_secs(1)
+        );
+        assert_eq!(session.performance().wpm().unwrap(), 120f32);
+    }
+
+    #[test]
+    fn test_personal_best() {
+        let mut first = session();
+        let start = Instant::now();
+        first.start(start).unwrap();
+        type_text(&mut first, "sphinx of ");
+        first.end(start + Duration::from_secs(20)).unwrap();
+        let record = Record::new(&first, SystemTime::now());
+
+        let bests = PersonalBests::new(std::iter::once(&record));
+        let variant = Variant {
+            punctuation: true,
+            ..Variant::default()
+        };
+        let mut punctuated = session()
+            .with_personal_bests(bests.clone())
+            .with_variant(variant);
+        punctuated.start(start).unwrap();
+        assert_eq!(punctuated.personal_best(), None);
+
+        let mut session = session().with_personal_bests(bests.clone());
+        assert_eq!(session.personal_best(), None);
+        session.start(start).unwrap();
+        assert_eq!(session.personal_best(), Some(&record.summary));
+        assert_eq!(
+            bests.get(&session.category(Duration::from_secs(25))),
+            Some(&record.summary)
+        );
+        assert_eq!(bests.get(&session.category(Duration::from_secs(10))), None);
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut session = session().with_auto_pause(Duration::from_secs(10));
+        let start = Instant::now();
+        assert!(session.pause(start).is_err());
+        session.start(start).unwrap();
+        session.press(Keystroke::Char('s'), start + Duration::from_secs(1));
+        assert!(!session.auto_pause(start + Duration::from_secs(5)));
+        session.pause(start + Duration::from_secs(2)).unwrap();
+        assert!(session.is_paused());
+        assert_eq!(
+            session.elapsed(start + Duration::from_secs(30)),
+            Duration::from_secs(2)
+        );
+        session.resume(start + Duration::from_secs(32)).unwrap();
+        session.input("sphinx ", start + Duration::from_secs(33));
+        assert_eq!(session.submissions()[0].duration, Duration::from_secs(2));
+        assert_eq!(session.submissions()[0].elapsed, Duration::from_secs(3));
+
+        assert!(!session.auto_pause(start + Duration::from_secs(42)));
+        assert!(session.auto_pause(start + Duration::from_secs(43)));
+        assert!(!session.auto_pause(start + Duration::from_secs(60)));
+        session.end(start + Duration::from_secs(60)).unwrap();
+        assert_eq!(
+            session.performance().duration().unwrap(),
+            Duration::from_secs(13)
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::{HashMap, VecDeque};
+use std::error;
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+const SENTENCE_ENDS: [char; 3] = ['.', '!', '?'];
+
+/// A word-level Markov model where each word is predicted from the `order`
+/// words before it, so an order of 1 models bigrams and 2 models trigrams.
+#[derive(Debug)]
+pub struct MarkovChain {
+    order: usize,
+    transitions: HashMap<Vec<String>, Vec<String>>,
+    starts: Vec<Vec<String>>,
+}
+
+impl MarkovChain {
+    pub fn new(corpus: &[String], order: usize) -> Result<MarkovChain, MarkovChainError> {
+        if order == 0 || corpus.len() <= order {
+            return Err(MarkovChainError);
+        }
+        let mut transitions: HashMap<Vec<String>, Vec<String>> = HashMap::new();
+        for window in corpus.windows(order + 1) {
+            let (state, next) = window.split_at(order);
+            transitions
+                .entry(state.to_vec())
+                .or_default()
+                .push(next[0].clone());
+        }
+        let starts = corpus
+            .windows(order + 1)
+            .enumerate()
+            .filter(|(i, _)| *i == 0 || corpus[i - 1].ends_with(&SENTENCE_ENDS[..]))
+            .map(|(_, window)| window[..order].to_vec())
+            .collect();
+        Ok(MarkovChain {
+            order,
+            transitions,
+            starts,
+        })
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    /// Generates words indefinitely, restarting from a sentence start
+    /// whenever the chain reaches a state with no recorded successor.
+    pub fn into_words<R: Rng>(self, rng: R) -> MarkovWords<R> {
+        MarkovWords {
+            chain: self,
+            rng,
+            state: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MarkovWords<R> {
+    chain: MarkovChain,
+    rng: R,
+    state: Vec<String>,
+    pending: VecDeque<String>,
+}
+
+impl<R: Rng> MarkovWords<R> {
+    fn restart(&mut self) {
+        let start = self.chain.starts.choose(&mut self.rng).unwrap();
+        self.pending.extend(start.iter().cloned());
+        self.state.clear();
+    }
+
+    fn emit(&mut self, word: String) -> String {
+        if self.state.len() == self.chain.order {
+            self.state.remove(0);
+        }
+        self.state.push(word.clone());
+        word
+    }
+}
+
+impl<R: Rng> Iterator for MarkovWords<R> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.pending.is_empty() {
+            let rng = &mut self.rng;
+            let next = self
+                .chain
+                .transitions
+                .get(&self.state)
+                .and_then(|successors| successors.choose(rng))
+                .cloned();
+            match next {
+                Some(word) => return Some(self.emit(word)),
+                None => self.restart(),
+            }
+        }
+        let word = self.pending.pop_front().unwrap();
+        Some(self.emit(word))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MarkovChainError;
+
+impl fmt::Display for MarkovChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The corpus is too small for the requested Markov order.")
+    }
+}
+
+impl error::Error for MarkovChainError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn corpus(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_bigrams() {
+        let corpus = corpus("the cat sat. the dog ran. a cat ran");
+        let chain = MarkovChain::new(&corpus, 1).unwrap();
+        let words = chain
+            .into_words(StdRng::seed_from_u64(0))
+            .take(200)
+            .collect::<Vec<String>>();
+        for pair in words.windows(2) {
+            let observed = corpus.windows(2).any(|bigram| bigram == pair);
+            let restarted = pair[0] == "ran" && ["the", "a"].contains(&pair[1].as_str());
+            assert!(observed || restarted, "Unexpected bigram {:?}", pair);
+        }
+    }
+
+    #[test]
+    fn test_starts() {
+        let corpus = corpus("the cat sat. a dog ran! my vow");
+        let chain = MarkovChain::new(&corpus, 2).unwrap();
+        assert_eq!(
+            chain.starts,
+            vec![corpus[0..2].to_vec(), corpus[3..5].to_vec()]
+        );
+    }
+
+    #[test]
+    fn test_corpus_too_small() {
+        assert!(MarkovChain::new(&corpus("sphinx of"), 2).is_err());
+        assert!(MarkovChain::new(&corpus("sphinx of black"), 0).is_err());
+    }
+}
@@ -0,0 +1,9 @@
+mod markov;
+mod punctuation;
+mod recorder;
+mod word_source;
+
+pub use markov::{MarkovChain, MarkovChainError, MarkovWords};
+pub use punctuation::Punctuate;
+pub use recorder::Recorder;
+pub use word_source::{IterSource, WordSource};
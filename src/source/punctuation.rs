@@ -0,0 +1,157 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+const NUMBER_PROBABILITY: f64 = 0.1;
+const MAX_NUMBER: u32 = 10_000;
+const WRAP_PROBABILITY: f64 = 0.05;
+const PAUSE_PROBABILITY: f64 = 0.1;
+const SENTENCE_END_PROBABILITY: f64 = 0.1;
+
+const WRAPS: [(&str, &str); 3] = [("\"", "\""), ("'", "'"), ("(", ")")];
+const PAUSES: [&str; 3] = [",", ";", ":"];
+const SENTENCE_ENDS: [&str; 3] = [".", "!", "?"];
+
+/// Decorates a stream of words with punctuation and numbers so that symbols
+/// can be practiced with any lexicon.
+#[derive(Debug)]
+pub struct Punctuate<I, R> {
+    words: I,
+    rng: R,
+    punctuation: bool,
+    numbers: bool,
+    sentence_start: bool,
+}
+
+impl<I, R> Punctuate<I, R>
+where
+    I: Iterator<Item = String>,
+    R: Rng,
+{
+    pub fn new(words: I, rng: R) -> Punctuate<I, R> {
+        Punctuate {
+            words,
+            rng,
+            punctuation: false,
+            numbers: false,
+            sentence_start: true,
+        }
+    }
+
+    pub fn with_punctuation(mut self, punctuation: bool) -> Punctuate<I, R> {
+        self.punctuation = punctuation;
+        self
+    }
+
+    pub fn with_numbers(mut self, numbers: bool) -> Punctuate<I, R> {
+        self.numbers = numbers;
+        self
+    }
+
+    fn punctuate(&mut self, word: String) -> String {
+        let mut word = if self.sentence_start {
+            self.sentence_start = false;
+            capitalize(&word)
+        } else {
+            word
+        };
+        if self.rng.gen_bool(WRAP_PROBABILITY) {
+            let (open, close) = WRAPS.choose(&mut self.rng).unwrap();
+            word = format!("{}{}{}", open, word, close);
+        }
+        if self.rng.gen_bool(SENTENCE_END_PROBABILITY) {
+            word.push_str(SENTENCE_ENDS.choose(&mut self.rng).unwrap());
+            self.sentence_start = true;
+        } else if self.rng.gen_bool(PAUSE_PROBABILITY) {
+            word.push_str(PAUSES.choose(&mut self.rng).unwrap());
+        }
+        word
+    }
+}
+
+impl<I, R> Iterator for Punctuate<I, R>
+where
+    I: Iterator<Item = String>,
+    R: Rng,
+{
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.numbers && self.rng.gen_bool(NUMBER_PROBABILITY) {
+            return Some(self.rng.gen_range(0, MAX_NUMBER).to_string());
+        }
+        let word = self.words.next()?;
+        if self.punctuation {
+            Some(self.punctuate(word))
+        } else {
+            Some(word)
+        }
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn words() -> impl Iterator<Item = String> {
+        ["sphinx", "of", "black", "quartz"]
+            .iter()
+            .cloned()
+            .map(String::from)
+            .cycle()
+    }
+
+    #[test]
+    fn test_disabled() {
+        let rng = StdRng::seed_from_u64(0);
+        let stream = Punctuate::new(words(), rng);
+        assert_eq!(
+            stream.take(4).collect::<Vec<String>>(),
+            vec!["sphinx", "of", "black", "quartz"]
+        );
+    }
+
+    #[test]
+    fn test_punctuation() {
+        let rng = StdRng::seed_from_u64(0);
+        let stream = Punctuate::new(words(), rng).with_punctuation(true);
+        let decorated = stream.take(200).collect::<Vec<String>>();
+        assert!(decorated[0]
+            .trim_start_matches(|c: char| !c.is_alphabetic())
+            .starts_with('S'));
+        for (word, original) in decorated.iter().zip(words()) {
+            assert!(word.to_lowercase().contains(&original));
+        }
+        assert!(decorated
+            .iter()
+            .any(|word| word.ends_with(|c| SENTENCE_ENDS.concat().contains(c))));
+    }
+
+    #[test]
+    fn test_numbers() {
+        let rng = StdRng::seed_from_u64(0);
+        let stream = Punctuate::new(words(), rng).with_numbers(true);
+        let decorated = stream.take(200).collect::<Vec<String>>();
+        let numbers = decorated
+            .iter()
+            .filter(|word| word.parse::<u32>().is_ok())
+            .count();
+        assert!(numbers > 0);
+        assert!(numbers < decorated.len());
+    }
+
+    #[test]
+    fn test_capitalize() {
+        assert_eq!(capitalize("élan"), "Élan");
+        assert_eq!(capitalize(""), "");
+    }
+}
@@ -0,0 +1,67 @@
+use std::sync::{Arc, Mutex};
+
+use crate::source::WordSource;
+
+/// Passes words through from another source while keeping a copy of each,
+/// so that the exact sequence can be replayed later.
+pub struct Recorder {
+    source: Box<dyn WordSource>,
+    words: Arc<Mutex<Vec<String>>>,
+}
+
+impl Recorder {
+    pub fn new(source: Box<dyn WordSource>) -> Recorder {
+        Recorder {
+            source,
+            words: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// A handle to the words drawn so far, which stays valid after the
+    /// recorder is moved into a model.
+    pub fn words(&self) -> Arc<Mutex<Vec<String>>> {
+        self.words.clone()
+    }
+}
+
+impl WordSource for Recorder {
+    fn next_word(&mut self) -> Option<String> {
+        let word = self.source.next_word()?;
+        self.words.lock().unwrap().push(word.clone());
+        Some(word)
+    }
+
+    fn total_words(&self) -> Option<usize> {
+        self.source.total_words()
+    }
+
+    fn name(&self) -> &str {
+        self.source.name()
+    }
+
+    fn description(&self) -> String {
+        self.source.description()
+    }
+
+    fn feedback(&mut self, expected: &str, entered: &str) {
+        self.source.feedback(expected, entered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::IterSource;
+
+    #[test]
+    fn test_records_words() {
+        let stream = vec!["sphinx", "of"].into_iter().map(String::from);
+        let mut recorder = Recorder::new(Box::new(IterSource::new(stream, "test")));
+        let words = recorder.words();
+        assert_eq!(recorder.next_word(), Some("sphinx".to_string()));
+        assert_eq!(*words.lock().unwrap(), vec!["sphinx"]);
+        assert_eq!(recorder.next_word(), Some("of".to_string()));
+        assert_eq!(recorder.next_word(), None);
+        assert_eq!(*words.lock().unwrap(), vec!["sphinx", "of"]);
+    }
+}
@@ -0,0 +1,63 @@
+/// Supplies the words shown to the user and learns how they were typed.
+pub trait WordSource: Send + Sync {
+    /// Returns the next word, or `None` once a finite source is exhausted.
+    fn next_word(&mut self) -> Option<String>;
+
+    /// The total number of words this source yields, if it is finite.
+    fn total_words(&self) -> Option<usize> {
+        None
+    }
+
+    /// A short identifier for the source, such as the lexicon name.
+    fn name(&self) -> &str;
+
+    fn description(&self) -> String {
+        self.name().to_owned()
+    }
+
+    /// Called with every submitted word so adaptive sources can react.
+    fn feedback(&mut self, _expected: &str, _entered: &str) {}
+}
+
+/// Adapts any iterator of words into a `WordSource`.
+#[derive(Debug)]
+pub struct IterSource<I> {
+    words: I,
+    name: String,
+    total_words: Option<usize>,
+}
+
+impl<I> IterSource<I>
+where
+    I: Iterator<Item = String> + Send + Sync,
+{
+    pub fn new(words: I, name: &str) -> IterSource<I> {
+        IterSource {
+            words,
+            name: name.to_owned(),
+            total_words: None,
+        }
+    }
+
+    pub fn with_total_words(mut self, total_words: usize) -> IterSource<I> {
+        self.total_words = Some(total_words);
+        self
+    }
+}
+
+impl<I> WordSource for IterSource<I>
+where
+    I: Iterator<Item = String> + Send + Sync,
+{
+    fn next_word(&mut self) -> Option<String> {
+        self.words.next()
+    }
+
+    fn total_words(&self) -> Option<usize> {
+        self.total_words
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -0,0 +1,194 @@
+use std::io;
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::sync::mpsc::{Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::consts;
+use crate::performance::PerformanceMonitor;
+use crate::session::{Observer, Submission};
+
+/// A telemetry event. Each event is sent to subscribers as a single line of
+/// JSON tagged with its type:
+///
+/// ```text
+/// {"type":"word","index":0,"expected":"the","entered":"teh","correct":false,"elapsed_ms":812,"duration_ms":350}
+/// {"type":"performance","correct":12,"attempted":14,"accuracy":0.857,"wpm":48.2,"elapsed_ms":15000,"ended":false}
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    Word {
+        index: usize,
+        expected: String,
+        entered: String,
+        correct: bool,
+        elapsed_ms: u64,
+        duration_ms: u64,
+    },
+    Performance {
+        correct: u32,
+        attempted: u32,
+        accuracy: f32,
+        wpm: f32,
+        elapsed_ms: u64,
+        ended: bool,
+    },
+}
+
+impl Event {
+    pub fn performance(performance: &PerformanceMonitor) -> Event {
+        Event::Performance {
+            correct: performance.correct(),
+            attempted: performance.attempted(),
+            accuracy: performance.accuracy().unwrap_or_default(),
+            wpm: performance.wpm().unwrap_or_default(),
+            elapsed_ms: performance.duration().unwrap_or_default().as_millis() as u64,
+            ended: performance.is_ended(),
+        }
+    }
+}
+
+impl From<&Submission> for Event {
+    fn from(submission: &Submission) -> Event {
+        Event::Word {
+            index: submission.index,
+            expected: submission.expected.clone(),
+            entered: submission.entered.clone(),
+            correct: submission.correct,
+            elapsed_ms: submission.elapsed.as_millis() as u64,
+            duration_ms: submission.duration.as_millis() as u64,
+        }
+    }
+}
+
+enum Command {
+    Publish(Event),
+    Flush(Sender<()>),
+}
+
+/// Streams events to every client connected to a TCP port, so overlays and
+/// leaderboards can follow a session live. Events are written by a
+/// background thread, so publishing never waits on a slow subscriber.
+#[derive(Clone, Debug)]
+pub struct Telemetry {
+    local_addr: SocketAddr,
+    subscribers: Arc<Mutex<Vec<TcpStream>>>,
+    commands: SyncSender<Command>,
+}
+
+impl Telemetry {
+    /// Accepts subscribers on `address` in the background.
+    pub fn bind<A: ToSocketAddrs>(address: A) -> io::Result<Telemetry> {
+        let listener = TcpListener::bind(address)?;
+        let (commands, receiver) = mpsc::sync_channel(consts::TELEMETRY_QUEUE_SIZE);
+        let telemetry = Telemetry {
+            local_addr: listener.local_addr()?,
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            commands,
+        };
+        let subscribers = telemetry.subscribers.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let timeout = Duration::from_millis(consts::TELEMETRY_WRITE_TIMEOUT_MS);
+                if stream.set_write_timeout(Some(timeout)).is_ok() {
+                    subscribers.lock().unwrap().push(stream);
+                }
+            }
+        });
+        let subscribers = telemetry.subscribers.clone();
+        thread::spawn(move || {
+            for command in receiver {
+                match command {
+                    Command::Publish(event) => write_event(&subscribers, &event),
+                    Command::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        Ok(telemetry)
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Queues `event` for every subscriber. The event is dropped if the
+    /// queue is full, rather than holding up the caller.
+    pub fn publish(&self, event: Event) {
+        let _ = self.commands.try_send(Command::Publish(event));
+    }
+
+    /// Waits until every event published so far has been written.
+    pub fn flush(&self) {
+        let (done, waiting) = mpsc::channel();
+        if self.commands.send(Command::Flush(done)).is_ok() {
+            let _ = waiting.recv();
+        }
+    }
+}
+
+/// Sends `event` to every subscriber, forgetting those that have gone away
+/// or have not kept up.
+fn write_event(subscribers: &Mutex<Vec<TcpStream>>, event: &Event) {
+    let mut line = serde_json::to_string(event).expect("Events are always serializable.");
+    line.push('\n');
+    subscribers
+        .lock()
+        .unwrap()
+        .retain(|mut stream| stream.write_all(line.as_bytes()).is_ok());
+}
+
+impl Observer for Telemetry {
+    fn on_submission(&mut self, submission: &Submission) {
+        self.publish(Event::from(submission));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::time::Instant;
+
+    #[test]
+    fn test_publish() {
+        let telemetry = Telemetry::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(telemetry.local_addr()).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while telemetry.subscribers.lock().unwrap().is_empty() {
+            assert!(Instant::now() < deadline, "Timed out.");
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let submission = Submission {
+            index: 0,
+            expected: "the".to_string(),
+            entered: "teh".to_string(),
+            correct: false,
+            elapsed: Duration::from_millis(812),
+            duration: Duration::from_millis(350),
+        };
+        telemetry.clone().on_submission(&submission);
+        telemetry.publish(Event::performance(&PerformanceMonitor::new()));
+        telemetry.flush();
+
+        let mut lines = BufReader::new(stream).lines();
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            "{\"type\":\"word\",\"index\":0,\"expected\":\"the\",\"entered\":\"teh\",\
+             \"correct\":false,\"elapsed_ms\":812,\"duration_ms\":350}"
+        );
+        assert_eq!(
+            lines.next().unwrap().unwrap(),
+            "{\"type\":\"performance\",\"correct\":0,\"attempted\":0,\"accuracy\":0.0,\
+             \"wpm\":0.0,\"elapsed_ms\":0,\"ended\":false}"
+        );
+    }
+}
@@ -4,22 +4,31 @@ use std::cmp::Ordering;
 use cursive::theme::Style;
 use cursive::utils::markup::StyledString;
 use cursive::utils::span::SpannedString;
-use cursive::views::{EditView, HideableView, LinearLayout, StackView, TextView};
+use cursive::views::{Dialog, EditView, HideableView, LinearLayout, StackView, TextView};
 use cursive::Cursive;
 
+use crate::compare::Comparison;
 use crate::consts;
-use crate::model::Model;
-use crate::performance::PerformanceMonitor;
+use crate::performance::{PerformanceMonitor, Summary};
+use crate::race;
+use crate::session::Snapshot;
+use crate::view::graph::get_sparkline;
+use crate::view::settings::Settings;
+use crate::view::theme::Styles;
 
-pub fn update_model_display<I>(siv: &mut Cursive, model: &Model<I>, current_word: &str)
-where
-    I: Iterator<Item = String>,
-{
+pub fn update_model_display(siv: &mut Cursive, snapshot: &Snapshot) {
+    let settings = siv.user_data::<Settings>().copied().unwrap_or_default();
     siv.call_on_id(consts::DISPLAY, |view: &mut TextView| {
-        view.set_content(get_styled_display(&model, current_word));
+        view.set_content(get_styled_display(snapshot, &settings));
     });
     siv.call_on_id(consts::ENTRY, |view: &mut EditView| {
-        let _callback = view.set_content(current_word);
+        let _callback = view.set_content(snapshot.entry.as_str());
+    });
+}
+
+pub fn update_race_display(siv: &mut Cursive, snapshot: &Snapshot) {
+    siv.call_on_id(consts::RACE, |view: &mut TextView| {
+        view.set_content(get_race_text(snapshot));
     });
 }
 
@@ -33,26 +42,89 @@ pub fn update_display_on_start(siv: &mut Cursive) {
     siv.focus_id(consts::ENTRY).unwrap();
 }
 
-pub fn update_performance_display(siv: &mut Cursive, performance_monitor: &PerformanceMonitor) {
+/// Hides the words behind a notice until `update_display_on_resume`.
+pub fn update_display_on_pause(siv: &mut Cursive) {
+    siv.call_on_id(consts::CORE, |view: &mut HideableView<LinearLayout>| {
+        view.hide();
+    });
+    siv.call_on_id(consts::STACK, |view: &mut StackView| {
+        view.add_fullscreen_layer(
+            Dialog::new()
+                .title(consts::FAST_FINGERS)
+                .content(TextView::new(consts::PAUSED)),
+        );
+    });
+}
+
+pub fn update_display_on_resume(siv: &mut Cursive) {
+    update_display_on_start(siv);
+}
+
+/// Shows the performance so far, with `personal_best` as the speed to beat.
+pub fn update_performance_display(
+    siv: &mut Cursive,
+    performance_monitor: &PerformanceMonitor,
+    personal_best: Option<&Summary>,
+) {
     siv.call_on_id(consts::PERFORMANCE, |view: &mut TextView| {
-        view.set_content(performance_monitor.to_string());
+        let wpm: Vec<f32> = performance_monitor
+            .samples()
+            .iter()
+            .map(|sample| sample.net_wpm)
+            .collect();
+        view.set_content(format!(
+            "{}\n{}\n{}",
+            performance_monitor,
+            get_sparkline(&wpm, consts::PERFORMANCE_COLS - 4),
+            get_target_text(performance_monitor.wpm().unwrap_or_default(), personal_best)
+        ));
     });
 }
 
-fn common_prefix(s1: &str, s2: &str) -> (String, String) {
-    let len = s1
-        .chars()
-        .zip(s2.chars())
-        .take_while(|(x, y)| x == y)
-        .count();
+fn get_target_text(wpm: f32, personal_best: Option<&Summary>) -> String {
+    match personal_best {
+        Some(best) => format!("PB: {:.0} WPM ({:+.0})", best.wpm, wpm - best.wpm),
+        None => String::new(),
+    }
+}
+
+fn common_prefix(s1: &str, s2: &str, comparison: &Comparison) -> (String, String) {
+    let len = comparison.common_prefix_len(s1, s2);
     let (prefix, suffix) = s1.split_at(len);
     (prefix.to_owned(), suffix.to_owned())
 }
 
+fn get_race_text(snapshot: &Snapshot) -> String {
+    let rivals = [("Ghost", snapshot.ghost), ("Pacer", snapshot.pacer)];
+    let bots = rivals
+        .iter()
+        .filter_map(|(name, position)| position.map(|position| (name, position)))
+        .map(|(name, position)| {
+            format!(
+                "{}: {}\n{}",
+                name,
+                position,
+                race::standing(snapshot.progress(), position)
+            )
+        });
+    let players = snapshot.players.iter().map(|(name, progress)| {
+        format!(
+            "{}: {}\n{}",
+            name,
+            progress,
+            race::standing(snapshot.progress(), progress.words)
+        )
+    });
+    bots.chain(players).join("\n")
+}
+
 fn get_styled_words(
     words: &[String],
     history: &[String],
     current_entry: &str,
+    comparison: &Comparison,
+    styles: &Styles,
+    markers: &[(usize, Style)],
 ) -> Vec<StyledString> {
     let get_entry = |pos: usize| match pos.cmp(&history.len()) {
         Ordering::Less => &history[pos],
@@ -61,26 +133,32 @@ fn get_styled_words(
     };
     let get_prefix_style = |pos: usize| match pos.cmp(&history.len()) {
         Ordering::Less => {
-            if history[pos] != words[pos] && history[pos].starts_with(&words[pos]) {
-                *consts::INCORRECT_STYLE
+            if !comparison.words_eq(&history[pos], &words[pos])
+                && comparison.common_prefix_len(&words[pos], &history[pos]) == words[pos].len()
+            {
+                styles.incorrect
             } else {
-                *consts::CORRECT_STYLE
+                styles.correct
             }
         }
-        Ordering::Equal => *consts::CORRECT_STYLE,
-        Ordering::Greater => *consts::FUTURE_STYLE,
+        Ordering::Equal => styles.correct,
+        Ordering::Greater => styles.future,
     };
     let get_suffix_style = |pos: usize| match pos.cmp(&history.len()) {
-        Ordering::Less => *consts::INCORRECT_STYLE,
-        Ordering::Equal => *consts::CURRENT_STYLE,
-        Ordering::Greater => *consts::FUTURE_STYLE,
+        Ordering::Less => styles.incorrect,
+        Ordering::Equal => styles.current,
+        Ordering::Greater => styles.future,
     };
     (0..words.len())
         .map(|pos| {
             let entry: &str = get_entry(pos);
-            let prefix_style: Style = get_prefix_style(pos);
-            let suffix_style: Style = get_suffix_style(pos);
-            let (prefix, suffix) = common_prefix(&words[pos], entry);
+            let mut prefix_style: Style = get_prefix_style(pos);
+            let mut suffix_style: Style = get_suffix_style(pos);
+            for (_, marker_style) in markers.iter().filter(|(marked, _)| *marked == pos) {
+                prefix_style = prefix_style.combine(*marker_style);
+                suffix_style = suffix_style.combine(*marker_style);
+            }
+            let (prefix, suffix) = common_prefix(&words[pos], entry, comparison);
             (
                 SpannedString::styled(prefix, prefix_style),
                 SpannedString::styled(suffix, suffix_style),
@@ -100,8 +178,11 @@ fn get_styled_string(
     history: &[String],
     current_entry: &str,
     row_width: usize,
+    comparison: &Comparison,
+    styles: &Styles,
+    markers: &[(usize, Style)],
 ) -> StyledString {
-    let styled_words = get_styled_words(words, history, current_entry);
+    let styled_words = get_styled_words(words, history, current_entry, comparison, styles, markers);
     styled_words
         .chunks(row_width)
         .map(|chunk| {
@@ -136,26 +217,171 @@ fn get_styled_string(
         })
 }
 
-fn get_styled_display<I>(model: &Model<I>, current_word: &str) -> StyledString
-where
-    I: Iterator<Item = String>,
-{
-    let words = model.get_words();
-    let history = model.get_history();
-    get_styled_string(&words, &history, current_word, consts::PANEL_COLS)
+/// Marks the words that rivals are currently on.
+fn get_markers(snapshot: &Snapshot, styles: &Styles) -> Vec<(usize, Style)> {
+    let rivals = [
+        (snapshot.ghost, styles.ghost),
+        (snapshot.pacer, styles.pacer),
+    ];
+    rivals
+        .iter()
+        .filter_map(|(position, style)| {
+            position
+                .and_then(|position| position.checked_sub(snapshot.offset))
+                .map(|position| (position, *style))
+        })
+        .collect()
+}
+
+fn get_styled_display(snapshot: &Snapshot, settings: &Settings) -> StyledString {
+    get_styled_string(
+        &snapshot.words,
+        &snapshot.history,
+        &snapshot.entry,
+        settings.panel_cols,
+        &snapshot.comparison,
+        &settings.styles,
+        &get_markers(snapshot, &settings.styles),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn styles() -> Styles {
+        Styles::default()
+    }
+
+    #[test]
+    fn test_target_text() {
+        let best = Summary {
+            wpm: 62.4,
+            ..Summary::default()
+        };
+        assert_eq!(get_target_text(58.9, Some(&best)), "PB: 62 WPM (-4)");
+        assert_eq!(get_target_text(70f32, Some(&best)), "PB: 62 WPM (+8)");
+        assert_eq!(get_target_text(70f32, None), "");
+    }
+
     #[test]
     fn test_common_prefix() {
-        let (prefix, suffix) = common_prefix("asdfgjk", "asfjkli");
+        let (prefix, suffix) = common_prefix("asdfgjk", "asfjkli", &Comparison::new());
         assert_eq!(prefix, "as");
         assert_eq!(suffix, "dfgjk");
     }
 
+    #[test]
+    fn test_common_prefix_unicode() {
+        let (prefix, suffix) = common_prefix("café", "cafe", &Comparison::new());
+        assert_eq!(prefix, "caf");
+        assert_eq!(suffix, "é");
+        let (prefix, suffix) = common_prefix("cafe\u{301}", "cafe", &Comparison::new());
+        assert_eq!(prefix, "caf");
+        assert_eq!(suffix, "e\u{301}");
+        let (prefix, suffix) = common_prefix("привет", "приве", &Comparison::new());
+        assert_eq!(prefix, "приве");
+        assert_eq!(suffix, "т");
+        let (prefix, suffix) = common_prefix("日本語", "日本", &Comparison::new());
+        assert_eq!(prefix, "日本");
+        assert_eq!(suffix, "語");
+    }
+
+    #[test]
+    fn test_get_styled_string_ignoring_accents() {
+        let words = ["école", "über"]
+            .iter()
+            .cloned()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        let history = ["ecole"]
+            .iter()
+            .cloned()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        let comparison = Comparison::new().with_ignore_accents(true);
+        let styled_string = get_styled_string(
+            &words,
+            &history,
+            "ub",
+            2,
+            &comparison,
+            &Styles::default(),
+            &[],
+        );
+        let mut expected = SpannedString::new();
+        expected.append(SpannedString::styled("école", styles().correct));
+        expected.append(SpannedString::styled("", styles().incorrect));
+        expected.append(" ");
+        expected.append(SpannedString::styled("üb", styles().correct));
+        expected.append(SpannedString::styled("er", styles().current));
+        assert_eq!(styled_string, expected);
+    }
+
+    #[test]
+    fn test_get_styled_string_with_ghost() {
+        let words = ["sphinx", "of", "black"]
+            .iter()
+            .cloned()
+            .map(String::from)
+            .collect::<Vec<String>>();
+        let styled_string = get_styled_string(
+            &words,
+            &[],
+            "",
+            3,
+            &Comparison::new(),
+            &Styles::default(),
+            &[(1, styles().ghost)],
+        );
+        let ghost_style = styles().future.combine(styles().ghost);
+        let mut expected = SpannedString::new();
+        expected.append(SpannedString::styled("", styles().correct));
+        expected.append(SpannedString::styled("sphinx", styles().current));
+        expected.append(" ");
+        expected.append(SpannedString::styled("of", ghost_style));
+        expected.append(SpannedString::styled("", ghost_style));
+        expected.append(" ");
+        expected.append(SpannedString::styled("black", styles().future));
+        expected.append(SpannedString::styled("", styles().future));
+        assert_eq!(styled_string, expected);
+    }
+
+    #[test]
+    fn test_race_text() {
+        let snapshot = Snapshot {
+            words: vec![],
+            history: vec!["of".to_string()],
+            entry: String::new(),
+            comparison: Comparison::new(),
+            offset: 2,
+            ghost: None,
+            pacer: Some(5),
+            players: vec![],
+        };
+        assert_eq!(get_race_text(&snapshot), "Pacer: 5\nBehind by 2");
+        let snapshot = Snapshot {
+            ghost: Some(1),
+            ..snapshot
+        };
+        assert_eq!(
+            get_race_text(&snapshot),
+            "Ghost: 1\nAhead by 2\nPacer: 5\nBehind by 2"
+        );
+        let progress = race::PlayerProgress {
+            words: 3,
+            wpm: 60.0,
+            finished: false,
+        };
+        let snapshot = Snapshot {
+            ghost: None,
+            pacer: None,
+            players: vec![("alice".to_string(), progress)],
+            ..snapshot
+        };
+        assert_eq!(get_race_text(&snapshot), "alice: 3 (60 wpm)\nLevel");
+    }
+
     #[test]
     fn test_get_styled_string() {
         let words = ["sphinx", "of", "black", "quartz", "judge"]
@@ -168,22 +394,30 @@ mod tests {
             .cloned()
             .map(String::from)
             .collect::<Vec<String>>();
-        let styled_string: StyledString = get_styled_string(&words, &history, "qu", 3);
+        let styled_string: StyledString = get_styled_string(
+            &words,
+            &history,
+            "qu",
+            3,
+            &Comparison::new(),
+            &Styles::default(),
+            &[],
+        );
         let mut expected = SpannedString::new();
-        expected.append(SpannedString::styled("sphinx", *consts::CORRECT_STYLE));
-        expected.append(SpannedString::styled("", *consts::INCORRECT_STYLE));
+        expected.append(SpannedString::styled("sphinx", styles().correct));
+        expected.append(SpannedString::styled("", styles().incorrect));
         expected.append(" ");
-        expected.append(SpannedString::styled("of", *consts::INCORRECT_STYLE));
-        expected.append(SpannedString::styled("", *consts::INCORRECT_STYLE));
+        expected.append(SpannedString::styled("of", styles().incorrect));
+        expected.append(SpannedString::styled("", styles().incorrect));
         expected.append(" ");
-        expected.append(SpannedString::styled("bl", *consts::CORRECT_STYLE));
-        expected.append(SpannedString::styled("ack", *consts::INCORRECT_STYLE));
+        expected.append(SpannedString::styled("bl", styles().correct));
+        expected.append(SpannedString::styled("ack", styles().incorrect));
         expected.append("\n");
-        expected.append(SpannedString::styled("qu", *consts::CORRECT_STYLE));
-        expected.append(SpannedString::styled("artz", *consts::CURRENT_STYLE));
+        expected.append(SpannedString::styled("qu", styles().correct));
+        expected.append(SpannedString::styled("artz", styles().current));
         expected.append(" ");
-        expected.append(SpannedString::styled("judge", *consts::FUTURE_STYLE));
-        expected.append(SpannedString::styled("", *consts::FUTURE_STYLE));
+        expected.append(SpannedString::styled("judge", styles().future));
+        expected.append(SpannedString::styled("", styles().future));
         assert_eq!(styled_string, expected);
     }
 }
@@ -0,0 +1,152 @@
+use std::time::Duration;
+
+use crate::performance::Sample;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const NET: char = '#';
+const RAW: char = ':';
+const ERROR: char = 'x';
+const LABEL_COLS: usize = 5;
+
+/// The last `width` values as a line of bars, scaled to the largest.
+pub fn get_sparkline(values: &[f32], width: usize) -> String {
+    let values = &values[values.len().saturating_sub(width)..];
+    let max = values.iter().cloned().fold(0f32, f32::max);
+    values
+        .iter()
+        .map(|value| match max > 0f32 {
+            true => BARS[(value / max * (BARS.len() - 1) as f32).round() as usize],
+            false => BARS[0],
+        })
+        .collect()
+}
+
+/// A bar chart of net WPM over raw WPM, with a mark under each column where
+/// mistakes were made. Samples are merged when there are more than `cols`.
+pub fn get_chart(samples: &[Sample], cols: usize, rows: usize) -> String {
+    let columns = merge(samples, cols.saturating_sub(LABEL_COLS).max(1));
+    let max = columns
+        .iter()
+        .map(|sample| sample.raw_wpm)
+        .fold(0f32, f32::max);
+    let height = |wpm: f32| match max > 0f32 {
+        true => (wpm / max * rows as f32).round() as usize,
+        false => 0,
+    };
+
+    let mut lines = Vec::new();
+    for row in (1..=rows).rev() {
+        let label = match row {
+            _ if row == rows => format!("{:>3.0}", max),
+            1 => format!("{:>3}", 0),
+            _ => "   ".to_string(),
+        };
+        let bars: String = columns
+            .iter()
+            .map(
+                |sample| match (height(sample.net_wpm), height(sample.raw_wpm)) {
+                    (net, _) if net >= row => NET,
+                    (_, raw) if raw >= row => RAW,
+                    _ => ' ',
+                },
+            )
+            .collect();
+        lines.push(format!("{} |{}", label, bars).trim_end().to_string());
+    }
+    lines.push(format!("    +{}", "-".repeat(columns.len())));
+    let errors: String = columns
+        .iter()
+        .map(|sample| match sample.errors {
+            0 => ' ',
+            _ => ERROR,
+        })
+        .collect();
+    lines.push(format!("     {}", errors).trim_end().to_string());
+    let end = columns
+        .last()
+        .map(|sample| sample.elapsed)
+        .unwrap_or_default();
+    lines.push(format!(
+        "     0s{:>width$}",
+        format!("{:.0}s", end.as_secs_f32()),
+        width = columns.len().saturating_sub(2).max(3)
+    ));
+    lines.push(format!(
+        "     {} net WPM  {} raw WPM  {} mistakes",
+        NET, RAW, ERROR
+    ));
+    lines.join("\n")
+}
+
+/// Merges consecutive samples so that there are at most `cols`, averaging
+/// the WPM of each group over its length and adding up its errors.
+fn merge(samples: &[Sample], cols: usize) -> Vec<Sample> {
+    let size = samples.len().div_ceil(cols);
+    let mut start = Duration::default();
+    samples
+        .chunks(size.max(1))
+        .map(|chunk| {
+            let (mut raw, mut net, mut previous) = (0f32, 0f32, start);
+            for sample in chunk.iter() {
+                let seconds = (sample.elapsed - previous).as_secs_f32();
+                raw += sample.raw_wpm * seconds;
+                net += sample.net_wpm * seconds;
+                previous = sample.elapsed;
+            }
+            let seconds = (previous - start).as_secs_f32();
+            start = previous;
+            let average = |wpm: f32| match seconds > 0f32 {
+                true => wpm / seconds,
+                false => 0f32,
+            };
+            Sample {
+                elapsed: previous,
+                raw_wpm: average(raw),
+                net_wpm: average(net),
+                errors: chunk.iter().map(|sample| sample.errors).sum(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(seconds: u64, raw_wpm: f32, net_wpm: f32, errors: u32) -> Sample {
+        Sample {
+            elapsed: Duration::from_secs(seconds),
+            raw_wpm,
+            net_wpm,
+            errors,
+        }
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(get_sparkline(&[], 4), "");
+        assert_eq!(get_sparkline(&[0f32, 0f32], 4), "▁▁");
+        assert_eq!(get_sparkline(&[10f32, 0f32, 30f32, 70f32], 4), "▂▁▄█");
+        assert_eq!(get_sparkline(&[70f32, 0f32, 70f32], 2), "▁█");
+    }
+
+    #[test]
+    fn test_chart() {
+        let samples = [
+            sample(1, 60f32, 60f32, 0),
+            sample(2, 60f32, 30f32, 1),
+            sample(3, 40f32, 40f32, 0),
+            sample(4, 45f32, 45f32, 0),
+        ];
+        assert_eq!(
+            get_chart(&samples, 10, 2),
+            " 60 |#: #\n  0 |####\n    +----\n      x\n     0s 4s\n     \
+             # net WPM  : raw WPM  x mistakes"
+        );
+        assert_eq!(
+            get_chart(&samples, 7, 2),
+            " 60 |#\n  0 |##\n    +--\n     x\n     0s 4s\n     \
+             # net WPM  : raw WPM  x mistakes"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::keyboard::{KeyStat, KeyStats, Layout};
+
+const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+/// 256-colour palette indices from blue to yellow, readable without telling
+/// red from green.
+const COLORS: [u8; 4] = [25, 30, 142, 220];
+
+/// What a heatmap colours the keys by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    Errors,
+    Latency,
+}
+
+impl Metric {
+    fn value(self, stat: &KeyStat) -> Option<f32> {
+        match self {
+            Metric::Errors => stat.error_rate(),
+            Metric::Latency => stat.average_latency().map(|latency| latency.as_secs_f32()),
+        }
+    }
+
+    fn format(self, value: f32) -> String {
+        match self {
+            Metric::Errors => format!("{:.0}%", value * 100f32),
+            Metric::Latency => format!("{:.0}ms", value * 1000f32),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Metric::Errors => write!(f, "errors"),
+            Metric::Latency => write!(f, "latency"),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Metric, String> {
+        match s {
+            "errors" => Ok(Metric::Errors),
+            "latency" => Ok(Metric::Latency),
+            _ => Err(format!(
+                "Unknown metric \"{}\", expected errors or latency",
+                s
+            )),
+        }
+    }
+}
+
+/// The keys of `layout` with a shade after each key, darker the higher its
+/// error rate or average latency. Keys never pressed have no shade. `color`
+/// also colours the shades with terminal escape codes.
+pub fn get_heatmap(stats: &KeyStats, layout: &Layout, metric: Metric, color: bool) -> String {
+    let values: Vec<f32> = layout
+        .rows()
+        .iter()
+        .flat_map(|row| row.chars())
+        .filter_map(|key| stats.get(key).and_then(|stat| metric.value(stat)))
+        .collect();
+    let min = match metric {
+        Metric::Errors => 0f32,
+        Metric::Latency => values.iter().cloned().fold(f32::INFINITY, f32::min),
+    };
+    let max = values.iter().cloned().fold(0f32, f32::max);
+    let level = |value: f32| match max > min {
+        true => ((value - min) / (max - min) * (SHADES.len() - 1) as f32).round() as usize,
+        false => 0,
+    };
+    let shade = |level: usize| match color {
+        true => format!("\x1b[38;5;{}m{}\x1b[0m", COLORS[level], SHADES[level]),
+        false => SHADES[level].to_string(),
+    };
+    let cell = |key: char| match stats.get(key).and_then(|stat| metric.value(stat)) {
+        Some(value) => format!("{}{}", key, shade(level(value))),
+        None => format!("{} ", key),
+    };
+
+    let mut lines: Vec<String> = layout
+        .rows()
+        .iter()
+        .enumerate()
+        .map(|(indent, row)| {
+            let keys: Vec<String> = row.chars().map(cell).collect();
+            format!("{}{}", " ".repeat(indent), keys.join(" "))
+                .trim_end()
+                .to_string()
+        })
+        .collect();
+    if !values.is_empty() {
+        let shades: Vec<String> = (0..SHADES.len()).map(shade).collect();
+        lines.push(format!(
+            "{} {} from {} to {}",
+            shades.join(""),
+            metric,
+            metric.format(min),
+            metric.format(max)
+        ));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_heatmap() {
+        let mut stats = KeyStats::new();
+        for _ in 0..3 {
+            stats.record('q', true, Some(Duration::from_millis(400)));
+        }
+        stats.record('q', false, Some(Duration::from_millis(400)));
+        stats.record('a', true, Some(Duration::from_millis(100)));
+        stats.record('/', false, Some(Duration::from_millis(200)));
+        let qwerty = Layout::default();
+
+        assert_eq!(
+            get_heatmap(&stats, &qwerty, Metric::Errors, false),
+            "1  2  3  4  5  6  7  8  9  0  -  =\n \
+             q▒ w  e  r  t  y  u  i  o  p  [  ]\n  \
+             a░ s  d  f  g  h  j  k  l  ;  '\n   \
+             z  x  c  v  b  n  m  ,  .  /█\n\
+             ░▒▓█ errors from 0% to 100%"
+        );
+        let latency = get_heatmap(&stats, &qwerty, Metric::Latency, false);
+        assert!(latency.contains("q█"));
+        assert!(latency.contains("a░"));
+        assert!(latency.contains("/▒"));
+        assert!(latency.ends_with("latency from 100ms to 400ms"));
+        assert!(
+            get_heatmap(&stats, &qwerty, Metric::Errors, true).contains("\x1b[38;5;220m█\x1b[0m")
+        );
+        let dvorak = get_heatmap(
+            &stats,
+            &Layout::built_in("dvorak").unwrap(),
+            Metric::Errors,
+            false,
+        );
+        assert!(dvorak.starts_with("1  2  3  4  5  6  7  8  9  0  [  ]\n '  ,  .  p"));
+        assert!(dvorak.contains("  a░ o"));
+        assert!(!get_heatmap(&KeyStats::new(), &qwerty, Metric::Errors, false).contains("from"));
+    }
+
+    #[test]
+    fn test_metric() {
+        assert_eq!("errors".parse(), Ok(Metric::Errors));
+        assert_eq!("latency".parse::<Metric>().unwrap().to_string(), "latency");
+        assert!("speed".parse::<Metric>().is_err());
+    }
+}
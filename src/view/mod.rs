@@ -1,7 +1,20 @@
 mod display;
+mod graph;
+mod heatmap;
+mod settings;
+mod theme;
 mod view_builder;
 
+pub use display::update_display_on_pause;
+pub use display::update_display_on_resume;
 pub use display::update_display_on_start;
 pub use display::update_model_display;
 pub use display::update_performance_display;
+pub use display::update_race_display;
+pub use graph::{get_chart, get_sparkline};
+pub use heatmap::{get_heatmap, Metric};
+pub use settings::Settings;
+pub use theme::{
+    parse_style, StyleDefinition, Styles, Theme, ThemeDefinition, ThemeError, DEFAULT_THEME,
+};
 pub use view_builder::ViewBuilder;
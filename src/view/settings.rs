@@ -0,0 +1,19 @@
+use crate::consts;
+use crate::view::theme::Styles;
+
+/// Display settings, kept in the `Cursive` user data so that the display
+/// updates can find them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    pub panel_cols: usize,
+    pub styles: Styles,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            panel_cols: consts::PANEL_COLS,
+            styles: Styles::default(),
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use cursive::theme::{Color, Effect, Palette, Style};
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_THEME: &str = "default";
+
+/// Themes shipped with the application, by name.
+const BUILT_IN_THEMES: [(&str, &str); 4] = [
+    (DEFAULT_THEME, include_str!("../../themes/default.toml")),
+    ("colorblind", include_str!("../../themes/colorblind.toml")),
+    ("dark", include_str!("../../themes/dark.toml")),
+    ("mono", include_str!("../../themes/mono.toml")),
+];
+
+const PALETTE_ROLES: [&str; 10] = [
+    "background",
+    "shadow",
+    "view",
+    "primary",
+    "secondary",
+    "tertiary",
+    "title_primary",
+    "title_secondary",
+    "highlight",
+    "highlight_inactive",
+];
+
+lazy_static! {
+    static ref THEME: Theme = ThemeDefinition::built_in(DEFAULT_THEME)
+        .and_then(|definition| definition.build().ok())
+        .expect("The default theme should be valid.");
+}
+
+/// How each part of the words panel is drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Styles {
+    pub correct: Style,
+    pub incorrect: Style,
+    pub current: Style,
+    pub future: Style,
+    pub ghost: Style,
+    pub pacer: Style,
+}
+
+impl Default for Styles {
+    fn default() -> Styles {
+        THEME.styles
+    }
+}
+
+/// The styles of the words panel together with the colours of the rest of
+/// the interface.
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub styles: Styles,
+    pub cursive: cursive::theme::Theme,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        THEME.clone()
+    }
+}
+
+/// Styles written as accepted by `parse_style`. Styles left out keep those
+/// of the default theme.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StyleDefinition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correct: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub incorrect: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub future: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ghost: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pacer: Option<String>,
+}
+
+/// A theme as written in TOML: the words panel styles, and colours for the
+/// Cursive palette roles such as `background` or `title_primary`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeDefinition {
+    /// Whether dialogs cast a shadow.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shadow: Option<bool>,
+    pub styles: StyleDefinition,
+    pub palette: BTreeMap<String, String>,
+}
+
+impl ThemeDefinition {
+    pub fn parse(text: &str) -> Result<ThemeDefinition, ThemeError> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn built_in(name: &str) -> Option<ThemeDefinition> {
+        BUILT_IN_THEMES
+            .iter()
+            .find(|(built_in, _)| *built_in == name)
+            .map(|(_, text)| ThemeDefinition::parse(text).expect("Built-in themes are valid."))
+    }
+
+    pub fn built_in_names() -> Vec<&'static str> {
+        BUILT_IN_THEMES.iter().map(|(name, _)| *name).collect()
+    }
+
+    /// Loads a built-in theme by name, or else a theme file.
+    pub fn load(name: &str) -> Result<ThemeDefinition, ThemeError> {
+        if let Some(definition) = ThemeDefinition::built_in(name) {
+            return Ok(definition);
+        }
+        let path = Path::new(name);
+        if !path.exists() {
+            return Err(ThemeError::Unknown(name.to_owned()));
+        }
+        ThemeDefinition::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Overrides every setting that `other` defines.
+    pub fn merge(&mut self, other: &ThemeDefinition) {
+        let styles = &mut self.styles;
+        let overrides = other.styles.clone();
+        styles.correct = overrides.correct.or_else(|| styles.correct.take());
+        styles.incorrect = overrides.incorrect.or_else(|| styles.incorrect.take());
+        styles.current = overrides.current.or_else(|| styles.current.take());
+        styles.future = overrides.future.or_else(|| styles.future.take());
+        styles.ghost = overrides.ghost.or_else(|| styles.ghost.take());
+        styles.pacer = overrides.pacer.or_else(|| styles.pacer.take());
+        self.shadow = other.shadow.or(self.shadow);
+        self.palette.extend(other.palette.clone());
+    }
+
+    /// Builds the theme, taking anything left out from the default theme.
+    pub fn build(&self) -> Result<Theme, ThemeError> {
+        let mut definition = ThemeDefinition::built_in(DEFAULT_THEME).unwrap();
+        definition.merge(self);
+        let styles = &definition.styles;
+        let parse = |name: &str, spec: &Option<String>| {
+            let spec = spec.as_deref().unwrap_or_default();
+            parse_style(spec).ok_or_else(|| ThemeError::Style {
+                name: name.to_owned(),
+                spec: spec.to_owned(),
+            })
+        };
+        let styles = Styles {
+            correct: parse("correct", &styles.correct)?,
+            incorrect: parse("incorrect", &styles.incorrect)?,
+            current: parse("current", &styles.current)?,
+            future: parse("future", &styles.future)?,
+            ghost: parse("ghost", &styles.ghost)?,
+            pacer: parse("pacer", &styles.pacer)?,
+        };
+
+        let mut cursive = cursive::theme::Theme::default();
+        if let Some(shadow) = definition.shadow {
+            cursive.shadow = shadow;
+        }
+        cursive.palette = get_palette(&definition.palette)?;
+        Ok(Theme { styles, cursive })
+    }
+}
+
+fn get_palette(colors: &BTreeMap<String, String>) -> Result<Palette, ThemeError> {
+    let mut palette = Palette::default();
+    for (role, spec) in colors.iter() {
+        if !PALETTE_ROLES.contains(&role.as_str()) {
+            return Err(ThemeError::Role(role.clone()));
+        }
+        let color = parse_color(spec).ok_or_else(|| ThemeError::Color {
+            role: role.clone(),
+            spec: spec.clone(),
+        })?;
+        palette.set_color(role, color);
+    }
+    Ok(palette)
+}
+
+fn parse_effect(name: &str) -> Option<Effect> {
+    match name {
+        "simple" => Some(Effect::Simple),
+        "reverse" => Some(Effect::Reverse),
+        "bold" => Some(Effect::Bold),
+        "italic" => Some(Effect::Italic),
+        "strikethrough" => Some(Effect::Strikethrough),
+        "underline" => Some(Effect::Underline),
+        _ => None,
+    }
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    // `Color::parse` panics on malformed hex codes.
+    if let Some(hex) = name.strip_prefix('#') {
+        let is_hex = hex.chars().all(|c| c.is_ascii_hexdigit());
+        if !is_hex || (hex.len() != 3 && hex.len() != 6) {
+            return None;
+        }
+    }
+    Color::parse(name)
+}
+
+/// Parses a style written as space separated effects and at most one
+/// colour, e.g. `"bold"`, `"reverse light red"` or `"underline #ff8800"`.
+pub fn parse_style(spec: &str) -> Option<Style> {
+    let mut style = Style::none();
+    let mut color = None;
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        if let Some(effect) = parse_effect(word) {
+            style = style.combine(effect);
+            continue;
+        }
+        if color.is_some() {
+            return None;
+        }
+        color = if word == "light" {
+            parse_color(&format!("light {}", words.next()?))
+        } else {
+            parse_color(word)
+        };
+        style = style.combine(color?);
+    }
+    Some(style)
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Unknown(String),
+    Style { name: String, spec: String },
+    Role(String),
+    Color { role: String, spec: String },
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ThemeError::Io(err) => write!(f, "Could not read theme: {}", err),
+            ThemeError::Toml(err) => write!(f, "Invalid theme: {}", err),
+            ThemeError::Unknown(name) => write!(
+                f,
+                "Unknown theme \"{}\", expected a file or one of: {}",
+                name,
+                ThemeDefinition::built_in_names().join(", ")
+            ),
+            ThemeError::Style { name, spec } => {
+                write!(f, "Invalid theme: unknown {} style \"{}\"", name, spec)
+            }
+            ThemeError::Role(role) => write!(f, "Invalid theme: unknown palette role \"{}\"", role),
+            ThemeError::Color { role, spec } => {
+                write!(f, "Invalid theme: unknown {} colour \"{}\"", role, spec)
+            }
+        }
+    }
+}
+
+impl error::Error for ThemeError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ThemeError::Io(err) => Some(err),
+            ThemeError::Toml(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ThemeError {
+    fn from(err: io::Error) -> ThemeError {
+        ThemeError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ThemeError {
+    fn from(err: toml::de::Error) -> ThemeError {
+        ThemeError::Toml(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cursive::theme::{BaseColor, PaletteColor};
+
+    #[test]
+    fn test_parse_style() {
+        assert_eq!(parse_style("bold"), Some(Style::from(Effect::Bold)));
+        assert_eq!(
+            parse_style("reverse red"),
+            Some(Style::from(Effect::Reverse).combine(Color::Dark(BaseColor::Red)))
+        );
+        assert_eq!(
+            parse_style("underline light blue"),
+            Some(Style::from(Effect::Underline).combine(Color::Light(BaseColor::Blue)))
+        );
+        assert_eq!(
+            parse_style("#ff8800"),
+            Some(Style::from(Color::Rgb(0xff, 0x88, 0x00)))
+        );
+        assert_eq!(parse_style("blinking"), None);
+        assert_eq!(parse_style("red blue"), None);
+        assert_eq!(parse_style("#ff88"), None);
+        assert_eq!(parse_style("light"), None);
+    }
+
+    #[test]
+    fn test_built_in_themes() {
+        for name in ThemeDefinition::built_in_names() {
+            ThemeDefinition::load(name).unwrap().build().unwrap();
+        }
+        let default = Theme::default();
+        assert_eq!(default.styles.correct, Style::from(Effect::Bold));
+        assert_eq!(default.cursive.palette, Palette::default());
+    }
+
+    #[test]
+    fn test_colorblind_theme_avoids_red_and_green() {
+        let definition = ThemeDefinition::built_in("colorblind").unwrap();
+        let specs = [&definition.styles.correct, &definition.styles.incorrect];
+        for spec in specs.iter() {
+            let spec = spec.as_deref().unwrap();
+            assert!(!spec.contains("red") && !spec.contains("green"));
+        }
+        let theme = definition.build().unwrap();
+        assert_ne!(theme.styles.correct, theme.styles.incorrect);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut definition = ThemeDefinition::built_in("dark").unwrap();
+        let overrides = ThemeDefinition::parse(
+            "shadow = true\n\
+             [styles]\n\
+             pacer = \"italic\"\n\
+             [palette]\n\
+             view = \"blue\"\n",
+        )
+        .unwrap();
+        definition.merge(&overrides);
+        let theme = definition.build().unwrap();
+        assert!(theme.cursive.shadow);
+        assert_eq!(theme.styles.pacer, Style::from(Effect::Italic));
+        assert_eq!(
+            theme.styles.correct,
+            parse_style("bold light green").unwrap()
+        );
+        let palette = &theme.cursive.palette;
+        assert_eq!(palette[PaletteColor::View], Color::Dark(BaseColor::Blue));
+        assert_eq!(
+            palette[PaletteColor::Background],
+            Color::Dark(BaseColor::Black)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        match ThemeDefinition::load("no-such-theme") {
+            Err(ThemeError::Unknown(_)) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        let definition = ThemeDefinition::parse("[palette]\nforeground = \"red\"\n").unwrap();
+        match definition.build() {
+            Err(ThemeError::Role(role)) => assert_eq!(role, "foreground"),
+            result => panic!("Unexpected result: {:?}", result.map(|theme| theme.styles)),
+        }
+        let definition = ThemeDefinition::parse("[palette]\nview = \"teal\"\n").unwrap();
+        match definition.build() {
+            Err(ThemeError::Color { role, .. }) => assert_eq!(role, "view"),
+            result => panic!("Unexpected result: {:?}", result.map(|theme| theme.styles)),
+        }
+    }
+}
@@ -6,7 +6,6 @@ use cursive::Cursive;
 
 use crate::consts;
 
-#[derive(Default)]
 pub struct ViewBuilder<F, G>
 where
     F: FnMut(&mut Cursive, &str, usize) + 'static,
@@ -15,6 +14,20 @@ where
     initial_words: Vec<String>,
     edit_callback: Option<Box<F>>,
     start_callback: Option<Box<G>>,
+    race_panel: bool,
+    greeting: String,
+    panel_cols: usize,
+    panel_rows: usize,
+}
+
+impl<F, G> Default for ViewBuilder<F, G>
+where
+    F: FnMut(&mut Cursive, &str, usize) + 'static,
+    G: Fn(&mut Cursive) + 'static,
+{
+    fn default() -> ViewBuilder<F, G> {
+        ViewBuilder::new()
+    }
 }
 
 impl<F, G> ViewBuilder<F, G>
@@ -27,6 +40,10 @@ where
             initial_words: Vec::new(),
             edit_callback: None,
             start_callback: None,
+            race_panel: false,
+            greeting: consts::GREETING.to_string(),
+            panel_cols: consts::PANEL_COLS,
+            panel_rows: consts::PANEL_ROWS,
         }
     }
 
@@ -45,10 +62,26 @@ where
         self
     }
 
+    pub fn with_panel_size(mut self, cols: usize, rows: usize) -> ViewBuilder<F, G> {
+        self.panel_cols = cols;
+        self.panel_rows = rows;
+        self
+    }
+
+    pub fn with_greeting(mut self, greeting: &str) -> ViewBuilder<F, G> {
+        self.greeting = greeting.to_owned();
+        self
+    }
+
+    pub fn with_race_panel(mut self) -> ViewBuilder<F, G> {
+        self.race_panel = true;
+        self
+    }
+
     pub fn build(self) -> IdView<StackView> {
         let display = TextView::empty()
             .with_id(consts::DISPLAY)
-            .fixed_size((consts::PANEL_COLS, consts::PANEL_ROWS));
+            .fixed_size((self.panel_cols, self.panel_rows));
 
         let performance = TextView::empty()
             .with_id(consts::PERFORMANCE)
@@ -56,6 +89,27 @@ where
         let entry = EditView::new()
             .on_edit_mut(self.edit_callback.unwrap())
             .with_id(consts::ENTRY);
+        let mut side_panel = LinearLayout::vertical().child(
+            Dialog::around(performance)
+                .title(consts::PERFORMANCE)
+                .fixed_width(consts::PERFORMANCE_COLS),
+        );
+        if self.race_panel {
+            let race = TextView::empty()
+                .with_id(consts::RACE)
+                .fixed_size((0, consts::RACE_ROWS));
+            side_panel.add_child(
+                Dialog::around(race)
+                    .title(consts::RACE)
+                    .fixed_width(consts::PERFORMANCE_COLS),
+            );
+        }
+        let mut start = Dialog::new()
+            .title(consts::FAST_FINGERS)
+            .content(TextView::new(self.greeting));
+        if let Some(start_callback) = self.start_callback {
+            start.add_button("Start", start_callback);
+        }
         StackView::new()
             .fullscreen_layer(
                 HideableView::new(
@@ -67,21 +121,12 @@ where
                                 .child(entry),
                         ))
                         .child(DummyView)
-                        .child(
-                            Dialog::around(performance)
-                                .title(consts::PERFORMANCE)
-                                .fixed_width(consts::PERFORMANCE_COLS),
-                        ),
+                        .child(side_panel),
                 )
                 .hidden()
                 .with_id(consts::CORE),
             )
-            .fullscreen_layer(
-                Dialog::new()
-                    .title(consts::FAST_FINGERS)
-                    .content(TextView::new("Type quickly."))
-                    .button("Start", self.start_callback.unwrap()),
-            )
+            .fullscreen_layer(start)
             .with_id(consts::STACK)
     }
 }